@@ -0,0 +1,58 @@
+/*
+cargo run --release --bin train -- <data> <output.nnue> [options]
+*/
+use std::{env, error::Error, process};
+
+use peripheral::nnue::{Network, Trainer, TrainingParams};
+
+const USAGE: &str = "Usage: train <data> <output.nnue> [options]
+
+Trains the NNUE network on a file of packed positions (see PackedPosition), and writes it after every epoch.
+
+Options:
+  --epochs <n>         Number of passes over the data (default 10)
+  --batch-size <n>     Positions per gradient step (default 16384)
+  --lr <x>             Learning rate (default 0.001)
+  --wdl <x>            Weight of the game result in the target, the rest is the score (default 0.3)
+  --threads <n>        Number of threads (default: all)
+  --shuffle <n>        Positions that are shuffled together (default 1048576)
+  --seed <n>           Seed for the initial weights and the shuffling (default 0)
+  --init <file>        Continue training from a network";
+
+fn main() {
+    if let Err(e) = run() {
+        println!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let (Some(data), Some(output)) = (args.first(), args.get(1)) else {
+        return Err(USAGE.into());
+    };
+
+    let mut params = TrainingParams::new();
+    let mut init = None;
+    let mut options = args[2..].iter();
+    while let Some(option) = options.next() {
+        let value = options.next().ok_or(format!("No value given for {}", option))?;
+        match option.as_str() {
+            "--epochs" => params.epochs = value.parse()?,
+            "--batch-size" => params.batch_size = value.parse()?,
+            "--lr" => params.learning_rate = value.parse()?,
+            "--wdl" => params.wdl = value.parse()?,
+            "--threads" => params.threads = value.parse()?,
+            "--shuffle" => params.shuffle_buffer = value.parse()?,
+            "--seed" => params.seed = value.parse()?,
+            "--init" => init = Some(Network::load(value)?),
+            other => return Err(format!("Unknown option: {}\n\n{}", other, USAGE).into())
+        }
+    }
+
+    let mut trainer = match init {
+        Some(network) => Trainer::from_network(&network, params),
+        None => Trainer::new(params)
+    };
+    trainer.train(data, output)
+}
@@ -1,10 +1,12 @@
 mod chess_engine;
 pub use chess_engine::ChessEngine;
 pub use chess_engine::SearchParams;
+pub use chess_engine::{MAX_SKILL_LEVEL, MIN_ELO, MAX_ELO, DEFAULT_BOOK_DEPTH};
 
 mod board;
 pub use board::Board;
 pub use board::zobrist;
+pub use board::{PackedPosition, epd_to_packed, packed_to_epd, PackedReader, PackedWriter, RESULT_BLACK_WIN, RESULT_DRAW, RESULT_WHITE_WIN};
 
 mod chess_move;
 pub use chess_move::Move;
@@ -27,11 +29,24 @@ mod move_list;
 pub use move_list::MoveList;
 
 mod eval;
-pub use eval::Eval;
+pub use eval::{Eval, EvalParams, EvalTrace, EvalBreakdown, EvalTerm, Tuner, WdlModel, WdlSample};
+pub use eval::nnue;
 
 mod perft;
 pub use perft::Perft;
 
+mod epd;
+pub use epd::EpdPosition;
+
+mod pgn;
+pub use pgn::{Game, GameResult, Line, MoveNode, PgnReader};
+
+mod book;
+pub use book::{Book, BookEntry};
+
+mod datagen;
+pub use datagen::Datagen;
+
 mod precomputed;
 pub mod util;
 
@@ -1,42 +1,25 @@
 
-use std::{error::Error, fmt::{Debug, Display}};
+use std::{fmt::{Debug, Display}, sync::Arc};
 
 use super::{
     Color, Color::*,
     PieceType, PieceType::*,
-    precomputed, Bitboard, Square, util, CastlingFlags,
+    precomputed, Bitboard, Square, Score, util, CastlingFlags,
+    eval::{EvalParams, default_params, GAME_PHASE_INCREMENT},
 };
+#[cfg(feature = "nnue")]
+use super::eval::nnue::{Network, Accumulator};
 
 pub mod zobrist;
+mod polyglot;
 mod make_move;
 mod undo_move;
 use self::{history::GSHistory, zobrist::*};
 mod parse_fen;
 mod history;
 use history::KeyHistory;
-
-struct FENdata<'a> {
-    rows: Vec<&'a str>,
-    color: char,
-    castling: &'a str,
-    en_passant: &'a str,
-    half_moves: usize,
-    full_moves: usize
-}
-
-impl<'a> FENdata<'a> {
-    fn try_parse(fen: &'a str) -> Result<Self, Box<dyn Error>> {
-        let mut parts = fen.split_ascii_whitespace();
-        Ok(Self {
-            rows: parts.next().ok_or("No row data")?.split('/').collect(),
-            color: parts.next().ok_or("No color data")?.chars().next().ok_or("No color char")?,
-            castling: parts.next().ok_or("No castling data")?,
-            en_passant: parts.next().ok_or("No en-passant data")?,
-            half_moves: parts.next().ok_or("No half move data")?.to_string().parse()?,
-            full_moves: parts.next().ok_or("No full move data")?.to_string().parse()?
-        })
-    }
-}
+mod packed;
+pub use packed::{PackedPosition, epd_to_packed, packed_to_epd, PackedReader, PackedWriter, RESULT_BLACK_WIN, RESULT_DRAW, RESULT_WHITE_WIN};
 
 #[derive(Clone, Copy, Debug)]
 pub struct GameState {
@@ -96,6 +79,19 @@ pub struct Board {
     pub piece_list: [Option<PieceType>; 64],
     pub gs: GameState,
     pub key: u64,
+    pub pawn_key: u64,
+
+    // Incrementally updated PeSTO eval (from white's perspective)
+    pub mg_score: Score,
+    pub eg_score: Score,
+    pub game_phase: i32,
+    params: Arc<EvalParams>,
+
+    // Incrementally updated hidden layer of the network, only while a network is set
+    #[cfg(feature = "nnue")]
+    pub accumulator: Accumulator,
+    #[cfg(feature = "nnue")]
+    network: Option<Arc<Network>>,
 
     gs_history: GSHistory,
     pub key_history: KeyHistory
@@ -108,6 +104,17 @@ impl Board {
             piece_list: [None; 64],
             gs: GameState::empty(),
             key: 0,
+            pawn_key: 0,
+
+            mg_score: 0,
+            eg_score: 0,
+            game_phase: 0,
+            params: default_params(),
+
+            #[cfg(feature = "nnue")]
+            accumulator: Accumulator::new(),
+            #[cfg(feature = "nnue")]
+            network: None,
 
             gs_history: GSHistory::new(),
             key_history: KeyHistory::new(0)
@@ -126,6 +133,19 @@ impl Board {
         self.piece_list[sq as usize] = Some(pt);
         self.bbs[pt as usize] ^= util::bitboard_from_square(sq);
         self.key ^= ZOBRIST_PIECE_SQUARE[pt as usize][sq as usize];
+        if pt == WPawn || pt == BPawn {
+            self.pawn_key ^= ZOBRIST_PIECE_SQUARE[pt as usize][sq as usize];
+        }
+
+        let pqt = self.params.pqts[pt as usize][sq as usize];
+        self.mg_score += pqt.mg as Score;
+        self.eg_score += pqt.eg as Score;
+        self.game_phase += GAME_PHASE_INCREMENT[pt as usize];
+
+        #[cfg(feature = "nnue")]
+        if let Some(network) = &self.network {
+            self.accumulator.add(network, pt as usize, sq);
+        }
     }
 
     pub fn remove_piece(&mut self, pt: PieceType, sq: Square) {
@@ -135,6 +155,19 @@ impl Board {
         self.piece_list[sq as usize] = None;
         self.bbs[pt as usize] ^= util::bitboard_from_square(sq);
         self.key ^= ZOBRIST_PIECE_SQUARE[pt as usize][sq as usize];
+        if pt == WPawn || pt == BPawn {
+            self.pawn_key ^= ZOBRIST_PIECE_SQUARE[pt as usize][sq as usize];
+        }
+
+        let pqt = self.params.pqts[pt as usize][sq as usize];
+        self.mg_score -= pqt.mg as Score;
+        self.eg_score -= pqt.eg as Score;
+        self.game_phase -= GAME_PHASE_INCREMENT[pt as usize];
+
+        #[cfg(feature = "nnue")]
+        if let Some(network) = &self.network {
+            self.accumulator.remove(network, pt as usize, sq);
+        }
     }
 
     pub fn move_piece(&mut self, pt: PieceType, from: Square, to: Square) {
@@ -179,6 +212,62 @@ impl Board {
         key
     }
 
+    pub fn make_pawn_key(&self) -> u64 {
+        let mut key = 0;
+
+        for pt in [WPawn, BPawn] {
+            let mut bb = self.bbs[pt as usize];
+            while bb != precomputed::EMPTY {
+                let sq = util::pop_ls1b(&mut bb);
+                key ^= ZOBRIST_PIECE_SQUARE[pt as usize][sq as usize];
+            }
+        }
+
+        key
+    }
+
+    // Full recompute of the incrementally updated eval terms: (mg_score, eg_score, game_phase)
+    pub fn make_psqt(&self) -> (Score, Score, i32) {
+        let mut mg = 0;
+        let mut eg = 0;
+        let mut game_phase = 0;
+
+        for (pt, pqts) in self.params.pqts.iter().enumerate() {
+            let mut pieces = self.bbs[pt];
+            while pieces != precomputed::EMPTY {
+                let sq = util::pop_ls1b(&mut pieces);
+                mg += pqts[sq as usize].mg as Score;
+                eg += pqts[sq as usize].eg as Score;
+                game_phase += GAME_PHASE_INCREMENT[pt];
+            }
+        }
+
+        (mg, eg, game_phase)
+    }
+
+    // The weights of the incremental piece-square eval, the default ones until refresh_psqt is called
+    pub fn eval_params(&self) -> &Arc<EvalParams> {
+        &self.params
+    }
+
+    // Has to be called with the weights of the Eval that evaluates this board
+    pub fn refresh_psqt(&mut self, params: Arc<EvalParams>) {
+        self.params = params;
+        (self.mg_score, self.eg_score, self.game_phase) = self.make_psqt();
+    }
+
+    #[cfg(feature = "nnue")]
+    pub fn network(&self) -> Option<&Arc<Network>> {
+        self.network.as_ref()
+    }
+
+    // Has to be called with the network of the Eval that evaluates this board, None stops updating the accumulator
+    #[cfg(feature = "nnue")]
+    pub fn refresh_accumulator(&mut self, network: Option<Arc<Network>>) {
+        self.accumulator = network.as_ref().map_or(Accumulator::new(), |network| Accumulator::refresh(network, self));
+        self.network = network;
+    }
+
     pub fn get_fen(&self) -> String {
         format!("{} {} {} {} {} {}",
             (0..8).rev().map(|y| {
@@ -1,5 +1,3 @@
-use std::mem;
-
 use super::{
     GameState,
     super::PieceType
@@ -11,34 +9,24 @@ pub struct GSHistoryEntry {
     pub captured_piece: Option<PieceType>
 }
 
-pub struct GSHistory {
-    history: [GSHistoryEntry; Self::MOVE_HISTORY_CAPACITY],
-    count: usize
-}
+// Grows past its initial capacity for long games
+pub struct GSHistory(Vec<GSHistoryEntry>);
 
 impl GSHistory {
     const MOVE_HISTORY_CAPACITY: usize = 512;
 
     pub fn new() -> Self {
-        Self {
-            history: unsafe {
-                let block: mem::MaybeUninit<[GSHistoryEntry; Self::MOVE_HISTORY_CAPACITY]> = mem::MaybeUninit::uninit();
-                block.assume_init()
-            },
-            count: 0
-        }
+        Self(Vec::with_capacity(Self::MOVE_HISTORY_CAPACITY))
     }
 
+    #[inline(always)]
     pub fn push(&mut self, gs: GSHistoryEntry) {
-        debug_assert!(self.count < Self::MOVE_HISTORY_CAPACITY);
-        self.history[self.count] = gs;
-        self.count += 1;
+        self.0.push(gs);
     }
 
+    #[inline(always)]
     pub fn pop(&mut self) -> GSHistoryEntry {
-        debug_assert!(self.count > 0);
-        self.count -= 1;
-        self.history[self.count]
+        self.0.pop().expect("History should not be empty")
     }
 }
 
@@ -99,6 +87,11 @@ impl KeyHistory {
             .count() >= 2
     }
 
+    // Plies since the last capture or pawn move
+    pub fn halfmove_clock(&self) -> usize {
+        self.0.last().expect("History should not be empty").get_count() - 1
+    }
+
     pub fn push_key(&mut self, key: u64, last_move_revertable: bool) {
         self.0.push(KeyHistoryEntry::new(key, if last_move_revertable {
             self.0.last().expect("History should not be empty").0 + 1 // add one to last entry's count
@@ -0,0 +1,284 @@
+use std::{error::Error, fs::{File, OpenOptions}, io::{self, BufRead, BufReader, BufWriter, Read, Write}};
+
+use super::{
+    Board,
+    super::{Bitboard, Square, Score, Color, CastlingFlags, EpdPosition, precomputed, util}
+};
+
+pub const RESULT_BLACK_WIN: u8 = 0;
+pub const RESULT_DRAW: u8 = 1;
+pub const RESULT_WHITE_WIN: u8 = 2;
+
+const NO_EN_PASSANT: u8 = 64;
+
+// EPD game results, indexed by the packed result
+const EPD_RESULTS: [&str; 3] = ["0-1", "1/2-1/2", "1-0"];
+const BLACK_TO_MOVE: u8 = 0b1000_0000;
+
+/*
+    A position with a score and game result in 32 bytes (all little endian):
+    0..8    occupancy bitboard
+    8..24   piece type (WPawn = 0 to BKing = 11) of every occupied square in ascending order, a nibble each, low nibble first
+    24      side to move (high bit) and en passant square (64 if none)
+    25      castling rights
+    26      halfmove clock
+    27..29  fullmove number
+    29..31  score (from white's perspective)
+    31      game result (0 = black win, 1 = draw, 2 = white win)
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PackedPosition {
+    pub occupancy: Bitboard,
+    pub pieces: [u8; 16],
+    pub player_to_move: Color,
+    pub en_passant: Option<Square>,
+    pub castling_rights: CastlingFlags,
+    pub halfmove_clock: u8,
+    pub fullmove_number: u16,
+    pub score: Score,
+    pub result: u8
+}
+
+impl PackedPosition {
+    pub const SIZE: usize = 32;
+
+    // The board does not keep track of the fullmove number, so it is set to 1
+    pub fn new(board: &Board, score: Score, result: u8) -> Self {
+        let occupancy = board.bbs[12] | board.bbs[13];
+        let mut pieces = [0; 16];
+        let mut squares = occupancy;
+        let mut i = 0;
+        while squares != precomputed::EMPTY && i < 32 {
+            let sq = util::pop_ls1b(&mut squares);
+            let pt = board.piece_list[sq as usize].expect("Occupied square should have a piece") as u8;
+            pieces[i / 2] |= pt << (4 * (i % 2));
+            i += 1;
+        }
+
+        Self {
+            occupancy,
+            pieces,
+            player_to_move: board.gs.player_to_move,
+            en_passant: (board.gs.en_passant_mask != precomputed::EMPTY).then(|| util::ls1b_from_bitboard(board.gs.en_passant_mask)),
+            castling_rights: board.gs.castling_rights,
+            halfmove_clock: board.key_history.halfmove_clock().min(u8::MAX as usize) as u8,
+            fullmove_number: 1,
+            score,
+            result
+        }
+    }
+
+    // (piece type, square) of every piece, in ascending square order
+    pub fn pieces(&self) -> impl Iterator<Item = (usize, Square)> + '_ {
+        let mut squares = self.occupancy;
+        (0..self.occupancy.count_ones() as usize).map(move |i| {
+            let sq = util::pop_ls1b(&mut squares);
+            (((self.pieces[i / 2] >> (4 * (i % 2))) & 0xf) as usize, sq)
+        })
+    }
+
+    // The result as a score for white: 0, 0.5 or 1
+    pub fn white_result(&self) -> f32 {
+        self.result as f32 / 2.0
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut piece_list = [None; 64];
+        for (pt, sq) in self.pieces() {
+            piece_list[sq as usize] = Some(pt);
+        }
+
+        let rows = (0..8).rev().map(|y| {
+            let mut row = String::new();
+            let mut empty_count = 0;
+            for x in 0..8 {
+                match piece_list[util::square_from_coord(x, y) as usize] {
+                    Some(pt) => {
+                        if empty_count > 0 {
+                            row.push_str(&empty_count.to_string());
+                            empty_count = 0;
+                        }
+                        row.push(util::piece_name_from_usize(pt));
+                    },
+                    None => empty_count += 1
+                }
+            }
+            if empty_count > 0 {
+                row.push_str(&empty_count.to_string());
+            }
+            row
+        }).collect::<Vec<String>>().join("/");
+
+        format!("{} {} {} {} {} {}",
+            rows,
+            self.player_to_move,
+            self.castling_rights,
+            self.en_passant.map_or("-", |sq| precomputed::SQUARE_NAMES[sq as usize]),
+            self.halfmove_clock,
+            self.fullmove_number
+        )
+    }
+
+    /*
+        The position as an EPD line with the result first, so it can be read by the tuner:
+        '<position> c9 "1-0"; ce 35; hmvc 0; fmvn 1;'. The score (ce) is from the side to move's perspective.
+     */
+    pub fn to_epd(&self) -> String {
+        let fen = self.to_fen();
+        let position = fen.split_ascii_whitespace().take(4).collect::<Vec<&str>>().join(" ");
+        format!("{} c9 \"{}\"; ce {}; hmvc {}; fmvn {};",
+            position,
+            EPD_RESULTS[self.result as usize],
+            self.score * self.player_to_move as Score,
+            self.halfmove_clock,
+            self.fullmove_number
+        )
+    }
+
+    // Reads the format of to_epd, in any order of the opcodes. Only the result (c9) is required
+    pub fn from_epd(line: &str) -> Result<Self, Box<dyn Error>> {
+        let epd = EpdPosition::parse(line)?;
+        let operand = |opcode: &str| epd.operation(opcode).and_then(|operands| operands.first());
+        let result = operand("c9").ok_or("No result (c9) in EPD")?;
+        let result = EPD_RESULTS.iter().position(|r| r == result).ok_or(format!("Invalid result: {}", result))? as u8;
+        let score = operand("ce").map_or(Ok(0), |score| score.parse::<Score>())?;
+
+        let board = epd.board();
+        let mut packed = Self::new(&board, score * board.gs.player_to_move as Score, result);
+        packed.halfmove_clock = operand("hmvc").map_or(Ok(0), |n| n.parse::<u8>())?;
+        packed.fullmove_number = operand("fmvn").map_or(Ok(1), |n| n.parse::<u16>())?;
+        Ok(packed)
+    }
+
+    pub fn to_board(&self) -> Result<Board, Box<dyn Error>> {
+        Board::try_from_fen(&self.to_fen())
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..8].copy_from_slice(&self.occupancy.to_le_bytes());
+        bytes[8..24].copy_from_slice(&self.pieces);
+        bytes[24] = self.en_passant.unwrap_or(NO_EN_PASSANT)
+            | if self.player_to_move == Color::Black {BLACK_TO_MOVE} else {0};
+        bytes[25] = self.castling_rights.bits();
+        bytes[26] = self.halfmove_clock;
+        bytes[27..29].copy_from_slice(&self.fullmove_number.to_le_bytes());
+        bytes[29..31].copy_from_slice(&self.score.to_le_bytes());
+        bytes[31] = self.result;
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Result<Self, Box<dyn Error>> {
+        let occupancy = Bitboard::from_le_bytes(bytes[0..8].try_into()?);
+        if occupancy.count_ones() > 32 {
+            return Err("More than 32 pieces".into());
+        }
+        let en_passant = bytes[24] & !BLACK_TO_MOVE;
+        if en_passant > NO_EN_PASSANT || bytes[25] > 0b1111 || bytes[31] > RESULT_WHITE_WIN {
+            return Err("Invalid packed position".into());
+        }
+
+        let position = Self {
+            occupancy,
+            pieces: bytes[8..24].try_into()?,
+            player_to_move: if bytes[24] & BLACK_TO_MOVE == 0 {Color::White} else {Color::Black},
+            en_passant: (en_passant != NO_EN_PASSANT).then_some(en_passant),
+            castling_rights: CastlingFlags::new(bytes[25]),
+            halfmove_clock: bytes[26],
+            fullmove_number: u16::from_le_bytes(bytes[27..29].try_into()?),
+            score: Score::from_le_bytes(bytes[29..31].try_into()?),
+            result: bytes[31]
+        };
+        if position.pieces().any(|(pt, _)| pt >= 12) {
+            return Err("Invalid piece type".into());
+        }
+        Ok(position)
+    }
+}
+
+// Reads packed positions one by one, so files don't have to fit in memory
+pub struct PackedReader<R: Read> {
+    reader: BufReader<R>
+}
+
+impl PackedReader<File> {
+    pub fn open(path: &str) -> io::Result<Self> {
+        Ok(Self::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> PackedReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader: BufReader::new(reader) }
+    }
+}
+
+impl<R: Read> Iterator for PackedReader<R> {
+    type Item = Result<PackedPosition, Box<dyn Error>>;
+
+    // Ends at the end of the input, a trailing partial entry is ignored
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut bytes = [0; PackedPosition::SIZE];
+        match self.reader.read_exact(&mut bytes) {
+            Ok(()) => Some(PackedPosition::from_bytes(&bytes)),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => None,
+            Err(e) => Some(Err(e.into()))
+        }
+    }
+}
+
+pub struct PackedWriter<W: Write> {
+    writer: BufWriter<W>
+}
+
+impl PackedWriter<File> {
+    pub fn create(path: &str) -> io::Result<Self> {
+        Ok(Self::new(File::create(path)?))
+    }
+
+    pub fn append(path: &str) -> io::Result<Self> {
+        Ok(Self::new(OpenOptions::new().create(true).append(true).open(path)?))
+    }
+}
+
+impl<W: Write> PackedWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer: BufWriter::new(writer) }
+    }
+
+    pub fn write(&mut self, position: &PackedPosition) -> io::Result<()> {
+        self.writer.write_all(&position.to_bytes())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// Converts an EPD file (see PackedPosition::from_epd) to packed positions, and returns the number of positions
+pub fn epd_to_packed(input: &str, output: &str) -> Result<usize, Box<dyn Error>> {
+    let mut writer = PackedWriter::create(output)?;
+    let mut count = 0;
+    for (i, line) in BufReader::new(File::open(input)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let position = PackedPosition::from_epd(&line).map_err(|e| format!("Line {}: {}", i + 1, e))?;
+        writer.write(&position)?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+pub fn packed_to_epd(input: &str, output: &str) -> Result<usize, Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(output)?);
+    let mut count = 0;
+    for position in PackedReader::open(input)? {
+        writeln!(writer, "{}", position?.to_epd())?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
@@ -25,8 +25,9 @@ impl<'a> FENdata<'a> {
             color: parts.next().ok_or("No color data")?.chars().next().ok_or("No color char")?,
             castling: parts.next().ok_or("No castling data")?,
             en_passant: parts.next().ok_or("No en-passant data")?,
-            half_moves: parts.next().ok_or("No half move data")?.to_string().parse()?,
-            full_moves: parts.next().ok_or("No full move data")?.to_string().parse()?
+            // Missing in EPD
+            half_moves: parts.next().map_or(Ok(0), str::parse)?,
+            full_moves: parts.next().map_or(Ok(1), str::parse)?
         })
     }
 }
@@ -0,0 +1,121 @@
+use std::{error::Error, fs};
+
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use super::{Board, Move, MoveGenerator, MoveList, precomputed};
+
+const ENTRY_SIZE: usize = 16;
+
+// Promotion pieces in bits 12..15 of a book move, starting at 1
+const PROMOTION_PIECES: &str = "nbrq";
+
+// A Polyglot book entry. All fields are big endian in the file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub mv: u16,
+    pub weight: u16,
+    pub learn: u32
+}
+
+impl BookEntry {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            key: u64::from_be_bytes(bytes[0..8].try_into().expect("Entry should have 8 key bytes")),
+            mv: u16::from_be_bytes(bytes[8..10].try_into().expect("Entry should have 2 move bytes")),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().expect("Entry should have 2 weight bytes")),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().expect("Entry should have 4 learn bytes"))
+        }
+    }
+
+    /*
+        The move in coordinate notation: bits 0..6 are the target square, 6..12 the origin square and 12..15 the promotion piece.
+        Castling is encoded as the king capturing its own rook, so e1h1 is e1g1 when the king is on e1.
+     */
+    pub fn move_name(&self, board: &Board) -> String {
+        let from = (self.mv >> 6 & 0x3f) as usize;
+        let mut to = (self.mv & 0x3f) as usize;
+        let promotion = (self.mv >> 12 & 0x7) as usize;
+
+        let king_on_start = board.piece_list[from].is_some_and(|pt| pt as usize % 6 == 5) && (from == 4 || from == 60);
+        if king_on_start && (to == from + 3 || to + 4 == from) {
+            to = if to > from { from + 2 } else { from - 2 };
+        }
+
+        let mut name = format!("{}{}", precomputed::SQUARE_NAMES[from], precomputed::SQUARE_NAMES[to]);
+        if let Some(piece) = PROMOTION_PIECES.chars().nth(promotion.wrapping_sub(1)) {
+            name.push(piece);
+        }
+        name
+    }
+}
+
+// An opening book in the Polyglot .bin format, with the entries sorted by key
+pub struct Book {
+    entries: Vec<BookEntry>
+}
+
+impl Book {
+    pub fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(format!("Book size is not a multiple of {} bytes", ENTRY_SIZE).into());
+        }
+        Ok(Self { entries: bytes.chunks_exact(ENTRY_SIZE).map(BookEntry::from_bytes).collect() })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // All entries of a position, found by binary search
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = start + self.entries[start..].partition_point(|entry| entry.key == key);
+        &self.entries[start..end]
+    }
+
+    // The legal book moves of the position with their weights
+    pub fn moves(&self, board: &mut Board, mg: &MoveGenerator) -> Vec<(Move, u16)> {
+        let entries = self.entries(board.polyglot_key());
+        if entries.is_empty() {
+            return Vec::new();
+        }
+
+        let mut legal_moves = MoveList::new();
+        mg.generate_legal_moves(board, &mut legal_moves, false);
+        let legal_moves = legal_moves.collect::<Vec<Move>>();
+        entries.iter()
+            .filter_map(|entry| {
+                let name = entry.move_name(board);
+                legal_moves.iter().find(|mv| mv.to_string() == name).map(|&mv| (mv, entry.weight))
+            })
+            .collect()
+    }
+
+    // A random book move, picked with a probability proportional to its weight
+    pub fn pick_move(&self, board: &mut Board, mg: &MoveGenerator, rng: &mut ChaCha8Rng) -> Option<Move> {
+        let moves = self.moves(board, mg);
+        let total = moves.iter().map(|(_, weight)| *weight as u32).sum::<u32>();
+        if total == 0 {
+            return None;
+        }
+
+        let mut pick = rng.gen_range(0..total);
+        for (mv, weight) in moves {
+            if pick < weight as u32 {
+                return Some(mv);
+            }
+            pick -= weight as u32;
+        }
+        None
+    }
+}
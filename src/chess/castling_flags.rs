@@ -5,7 +5,7 @@ use bitflags::bitflags;
 use super::{Square, precomputed};
 
 bitflags! {
-    #[derive(Clone, Copy, Debug)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
     pub struct CastlingFlags: u8 {
         const WK = 0b0000_0001;
         const WQ = 0b0000_0010;
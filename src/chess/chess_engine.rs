@@ -1,18 +1,31 @@
-use std::{error::Error, time::Instant};
+use std::{error::Error, sync::Arc};
 
-use super::{Board, MoveGenerator, Move};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use super::{Board, MoveGenerator, MoveList, Move, Score, Game, Book, WdlModel, Eval, EvalParams};
+#[cfg(feature = "nnue")]
+use super::eval::nnue;
 
 mod search;
-use search::TranspositionTable;
+use search::{TranspositionTable, TimeManager};
+
+mod skill;
+use skill::Skill;
+pub use skill::{MAX_SKILL_LEVEL, MIN_ELO, MAX_ELO};
 
 pub const MAX_DEPTH: usize = 64;
+pub const DEFAULT_BOOK_DEPTH: u8 = 20;
 pub struct SearchParams {
     pub move_time: Option<u128>,
     pub wtime: u128,
     pub btime: u128,
     pub winc: u128,
     pub binc: u128,
-    pub depth: u8
+    pub moves_to_go: Option<u128>,
+    pub move_overhead: u128,
+    pub depth: u8,
+    pub nodes: u64
 }
 
 impl SearchParams {
@@ -23,23 +36,52 @@ impl SearchParams {
             btime: u128::MAX,
             winc: 0,
             binc: 0,
-            depth: u8::MAX
+            moves_to_go: None,
+            move_overhead: 0,
+            depth: u8::MAX,
+            nodes: u64::MAX
         }
     }
 }
 
+// The result of the last search, so the move it picked can be annotated when it is made
+#[derive(Clone, Copy)]
+struct SearchEval {
+    key: u64,
+    mv: Move,
+    // From white's perspective
+    score: Score,
+    depth: u8
+}
+
 pub struct ChessEngine {
     board: Board,
     mg: MoveGenerator,
     tt: TranspositionTable,
+    eval: Eval,
 
-    timer: Instant,
+    tm: TimeManager,
+    node_limit: u64,
     nodes: u64,
+    best_move_nodes: u64,
     seldepth: u8,
-    search_time: u128,
     search_canceled: bool,
 
-    uci_moves: Vec<Move>
+    skill: Skill,
+    rng: ChaCha8Rng,
+    contempt: Score,
+    show_wdl: bool,
+    wdl_model: WdlModel,
+    root_scores: Vec<(Move, Score)>,
+
+    book: Option<Book>,
+    // In moves, counted from the last set_board
+    book_depth: u8,
+
+    start_fen: String,
+    uci_moves: Vec<Move>,
+    move_evals: Vec<Option<SearchEval>>,
+    last_eval: Option<SearchEval>
 }
 
 impl ChessEngine {
@@ -48,14 +90,29 @@ impl ChessEngine {
             board: Board::try_from_fen(fen).expect("Invalid fen"),
             mg: MoveGenerator::new(),
             tt: TranspositionTable::new(table_size),
+            eval: Eval::new(),
 
-            timer: Instant::now(),
+            tm: TimeManager::new(),
+            node_limit: u64::MAX,
             nodes: 0,
+            best_move_nodes: 0,
             seldepth: 0,
-            search_time: 0,
             search_canceled: false,
-            
-            uci_moves: Vec::new()
+
+            skill: Skill::new(),
+            rng: ChaCha8Rng::from_entropy(),
+            contempt: 0,
+            show_wdl: false,
+            wdl_model: WdlModel::new(),
+            root_scores: Vec::new(),
+
+            book: None,
+            book_depth: DEFAULT_BOOK_DEPTH,
+
+            start_fen: fen.trim().to_string(),
+            uci_moves: Vec::new(),
+            move_evals: Vec::new(),
+            last_eval: None
         }
     }
 
@@ -63,8 +120,79 @@ impl ChessEngine {
         self.tt = TranspositionTable::new(size_mb);
     }
 
+    pub fn set_skill_level(&mut self, level: u8) {
+        self.skill.level = level;
+    }
+
+    pub fn set_limit_strength(&mut self, limit_strength: bool) {
+        self.skill.limit_strength = limit_strength;
+    }
+
+    pub fn set_elo(&mut self, elo: u16) {
+        self.skill.elo = elo;
+    }
+
+    pub fn set_contempt(&mut self, contempt: Score) {
+        self.contempt = contempt;
+    }
+
+    pub fn set_show_wdl(&mut self, show_wdl: bool) {
+        self.show_wdl = show_wdl;
+    }
+
+    pub fn set_wdl_model(&mut self, wdl_model: WdlModel) {
+        self.wdl_model = wdl_model;
+    }
+
+    // None disables the book
+    pub fn set_book(&mut self, book: Option<Book>) {
+        self.book = book;
+    }
+
+    pub fn set_book_depth(&mut self, depth: u8) {
+        self.book_depth = depth;
+    }
+
+    fn book_move(&mut self) -> Option<Move> {
+        let book = self.book.as_ref()?;
+        if self.uci_moves.len() >= 2 * self.book_depth as usize {
+            return None;
+        }
+        book.pick_move(&mut self.board, &self.mg, &mut self.rng)
+    }
+
+    // Replaces the eval weights, and refreshes everything that was computed with the old ones
+    pub fn set_eval_params(&mut self, params: EvalParams) {
+        let params = Arc::new(params);
+        self.board.refresh_psqt(params.clone());
+        self.eval.set_params(params);
+    }
+
+    pub fn eval_params(&self) -> &Arc<EvalParams> {
+        self.eval.params()
+    }
+
+    // None goes back to the handcrafted eval
+    #[cfg(feature = "nnue")]
+    pub fn set_network(&mut self, network: Option<nnue::Network>) {
+        let network = network.map(Arc::new);
+        self.board.refresh_accumulator(network.clone());
+        self.eval.set_network(network);
+    }
+
+    #[cfg(feature = "nnue")]
+    pub fn network(&self) -> Option<&Arc<nnue::Network>> {
+        self.eval.network()
+    }
+
     pub fn set_board(&mut self, fen: &str) -> Result<(), Box<dyn Error>> {
         self.board = Board::try_from_fen(fen)?;
+        self.board.refresh_psqt(self.eval.params().clone());
+        #[cfg(feature = "nnue")]
+        self.board.refresh_accumulator(self.eval.network().cloned());
+        self.start_fen = fen.trim().to_string();
+        self.uci_moves.clear();
+        self.move_evals.clear();
         Ok(())
     }
 
@@ -73,17 +201,71 @@ impl ChessEngine {
     }
 
     pub fn make_uci_move(&mut self, mv: &str) -> Result<(), Box<dyn Error>> {
-        let mv = Move::try_from_str(mv, &self.board)?;
+        self.make_move(Move::try_from_str(mv, &self.board)?);
+        Ok(())
+    }
+
+    pub fn make_move(&mut self, mv: Move) {
+        let key = self.board.key;
+        // Moves parsed from coordinate notation have no capture flag
+        self.move_evals.push(self.last_eval.filter(|eval| eval.key == key && eval.mv.to_string() == mv.to_string()));
         self.board.make_move(&mv);
         self.uci_moves.push(mv);
-        Ok(())
+    }
+
+    // Also updates the check flag of the board
+    pub fn legal_moves(&mut self) -> MoveList {
+        let mut moves = MoveList::new();
+        self.mg.generate_legal_moves(&mut self.board, &mut moves, false);
+        moves
     }
 
     pub fn undo_move(&mut self) -> Result<(), &str>{
         self.board.undo_move(&self.uci_moves.pop().ok_or("No move to undo")?);
+        self.move_evals.pop();
         Ok(())
     }
 
+    // The moves made since the last set_board, with the evals of the searches that picked them
+    pub fn game(&self) -> Result<Game, Box<dyn Error>> {
+        let mut game = Game::from_moves(&self.start_fen, &self.uci_moves)?;
+        for (node, eval) in game.mainline.moves.iter_mut().zip(&self.move_evals) {
+            if let Some(eval) = eval {
+                node.add_eval(eval.score, eval.depth);
+            }
+        }
+        Ok(game)
+    }
+
+    /*
+        Plays the opponent's first move of a puzzle in the Lichess format, then searches for every solver move.
+        A different move that gives checkmate also solves the puzzle. None if the puzzle is invalid.
+     */
+    pub fn solve_puzzle(&mut self, fen: &str, moves: &str, nodes: u64) -> Option<bool> {
+        self.set_board(fen).ok()?;
+        let moves = moves.split_ascii_whitespace().collect::<Vec<&str>>();
+        if moves.len() < 2 {
+            return None;
+        }
+
+        for (i, expected) in moves.iter().enumerate() {
+            if !self.legal_moves().any(|mv| mv.to_string() == *expected) {
+                return None;
+            }
+            if i % 2 == 1 {
+                let mut search_params = SearchParams::new();
+                search_params.nodes = nodes;
+                let (mv, _) = self.search(search_params, false);
+                if mv.to_string() != *expected {
+                    self.make_move(mv);
+                    return Some(*self.legal_moves().get_count() == 0 && self.board.gs.is_in_check);
+                }
+            }
+            self.make_uci_move(expected).ok()?;
+        }
+        Some(true)
+    }
+
     pub fn probe_tt(&self) -> String {
         let entry = self.tt.get_entry_at_index(self.tt.calc_index(self.board.key));
         if TranspositionTable::entry_is_position(entry, self.board.key) {
@@ -1,8 +1,7 @@
-use std::time::Instant;
 use super::{
-    ChessEngine, SearchParams, MAX_DEPTH,
+    ChessEngine, SearchParams, SearchEval, MAX_DEPTH, Skill, skill::SKILL_MULTI_PV,
     super::{
-        Move, Score, MIN_SCORE, MAX_SCORE, CHECKMATE_SCORE, Eval, MoveList, Color, grade
+        Move, Score, MIN_SCORE, MAX_SCORE, CHECKMATE_SCORE, Eval, MoveList, grade, util
     }
 };
 
@@ -10,73 +9,119 @@ mod transposition_table;
 pub use transposition_table::TranspositionTable;
 use transposition_table::{TTProbeResult, TTEntry, NodeType};
 
+mod time_manager;
+pub use time_manager::TimeManager;
+
 impl ChessEngine {
     pub fn search(&mut self, search_params: SearchParams, verbose: bool) -> (Move, Score) {
-        self.timer = Instant::now();
-        self.search_time = search_params.move_time.unwrap_or(
-            match self.board.gs.player_to_move {
-                Color::White => search_params.wtime / 20 + search_params.winc / 2,
-                Color::Black => search_params.btime / 20 + search_params.binc / 2,
+        if let Some(mv) = self.book_move() {
+            self.last_eval = None;
+            if verbose {
+                println!("info string book move");
+                println!("bestmove {}", mv);
             }
-        );
+            return (mv, 0);
+        }
+
+        self.tm.start(&search_params, self.board.gs.player_to_move);
+        self.node_limit = std::cmp::min(search_params.nodes, self.skill.node_limit());
+        self.nodes = 0;
+        self.search_canceled = false;
 
         let mut pv = Vec::new();
+        let mut candidates = Vec::new();
 
         let tt_index = self.tt.calc_index(self.board.key);
         let mut last_search = TTEntry::empty();
 
-        for current_depth in 1..=std::cmp::min(search_params.depth, MAX_DEPTH as u8) {
+        let max_depth = std::cmp::min(std::cmp::min(search_params.depth, self.skill.depth_limit()), MAX_DEPTH as u8);
+        for current_depth in 1..=max_depth {
             self.tt.next_generation();
-            self.nodes = 0;
             self.seldepth = 0;
-            self.search_canceled = false;
+            self.best_move_nodes = 0;
+            self.root_scores.clear();
+            let iteration_start_nodes = self.nodes;
 
             self.negamax(MIN_SCORE, MAX_SCORE, current_depth as i8, 0, true);
 
+            if self.search_canceled {
+                break; // keep the result of the last completed iteration
+            }
+            candidates.clone_from(&self.root_scores);
+
             last_search = self.tt.get_entry(tt_index, self.board.key).expect("TT should include root");
 
             pv = self.tt.get_pv(&mut self.board, current_depth);
 
             if verbose {
-                self.print_search_info(last_search, current_depth, &pv);
+                let score = self.unbiased_score(last_search.score, &pv);
+                self.print_search_info(score, current_depth, &pv);
             }
 
-            if self.timer.elapsed().as_millis() >= self.search_time {
+            self.tm.iteration_finished(current_depth, last_search.best_move, last_search.score, self.best_move_nodes, self.nodes - iteration_start_nodes);
+            if self.tm.soft_limit_reached() || self.nodes >= self.node_limit {
                 break;
             }
         }
 
+        let (best_move, score) = if self.skill.enabled() && candidates.len() > 1 {
+            candidates.sort_by_key(|x| std::cmp::Reverse(x.1));
+            candidates.truncate(SKILL_MULTI_PV);
+            let mv = self.skill.pick_move(&candidates, &mut self.rng);
+            *candidates.iter().find(|x| x.0 == mv).expect("Picked move should be a candidate")
+        } else {
+            (last_search.best_move, last_search.score)
+        };
+        // Only the line of the best move is known, so the score of another picked move keeps its draw bias
+        let score = if pv.first() == Some(&best_move) {self.unbiased_score(score, &pv)} else {score};
+
+        self.last_eval = Some(SearchEval {
+            key: self.board.key,
+            mv: best_move,
+            score: score * self.board.gs.player_to_move as Score,
+            depth: last_search.depth
+        });
+
         if verbose {
-            let ponder = pv.get(1);
+            let ponder = pv.get(1).filter(|_| pv[0] == best_move);
             if let Some(mv) = ponder {
-                println!("bestmove {} ponder {}", pv[0], mv);
+                println!("bestmove {} ponder {}", best_move, mv);
             } else {
-                println!("bestmove {}", pv[0]);
+                println!("bestmove {}", best_move);
             }
         }
-        (last_search.best_move, last_search.score)
+        (best_move, score)
     }
 
-    fn print_search_info(&self, last_search: TTEntry, depth: u8, pv: &Vec<Move>) {
-        let elapsed = self.timer.elapsed().as_millis();
+    // Searches without the book and the Skill Level and UCI_Elo limits, which stay set for the next search
+    pub fn search_full_strength(&mut self, search_params: SearchParams, verbose: bool) -> (Move, Score) {
+        let book = self.book.take();
+        let skill = std::mem::replace(&mut self.skill, Skill::new());
+        let result = self.search(search_params, verbose);
+        self.book = book;
+        self.skill = skill;
+        result
+    }
 
-        let mate_score = {
-            let unsigned_mate_in_plies = CHECKMATE_SCORE - last_search.score.abs();
-            if unsigned_mate_in_plies < 100 {
-                Some(unsigned_mate_in_plies / 2 * last_search.score.signum())
-            } else {
-                None
-            }
-        };
+    fn print_search_info(&self, score: Score, depth: u8, pv: &Vec<Move>) {
+        let elapsed = self.tm.elapsed();
+
+        let mate_score = util::mate_in_moves(score);
 
         println!(
-            "info depth {} seldepth {} score {} nodes {} nps {} hashfull {} time {} pv{}",
+            "info depth {} seldepth {} score {}{} nodes {} nps {} hashfull {} time {} pv{}",
             depth,
             self.seldepth,
             if let Some(mate) = mate_score {
                 format!("mate {}", mate)
             } else {
-                format!("cp {}", last_search.score)
+                format!("cp {}", score)
+            },
+            if self.show_wdl {
+                let (win, draw, loss) = self.wdl_model.wdl(score, Eval::game_phase(&self.board));
+                format!(" wdl {} {} {}", win, draw, loss)
+            } else {
+                String::new()
             },
             self.nodes,
             if elapsed == 0 { 0 } else { self.nodes as u128 * 1000 / elapsed },
@@ -86,29 +131,66 @@ impl ChessEngine {
         );
     }
 
+    // Draws are worth -contempt for the side to move at the root, so positive contempt avoids draws
+    #[inline(always)]
+    fn draw_score(&self, ply: u8) -> Score {
+        if ply & 1 == 0 {-self.contempt} else {self.contempt}
+    }
+
+    // The root score without the contempt bias: a line that ends in a draw the search scored with draw_score is reported as 0
+    fn unbiased_score(&mut self, score: Score, pv: &[Move]) -> Score {
+        if self.contempt == 0 || score != self.draw_score(0) {
+            return score;
+        }
+
+        for mv in pv {
+            self.board.make_move(mv);
+        }
+        // The same draw conditions as negamax
+        let ply = pv.len();
+        let repetition = if ply <= 1 {self.board.key_history.contains_3fold()} else {self.board.key_history.contains_2fold()};
+        let mut moves = MoveList::new();
+        self.mg.generate_legal_moves(&mut self.board, &mut moves, false);
+        let stalemate = *moves.get_count() == 0 && !self.board.gs.is_in_check;
+        for mv in pv.iter().rev() {
+            self.board.undo_move(mv);
+        }
+
+        if repetition || stalemate {score - self.draw_score(0)} else {score}
+    }
+
+    // Check for timeout every 2048 nodes. Every node count is seen by exactly one node, as nodes are counted when entering them.
+    // The first iteration is never aborted, so there is always a move to play.
+    fn check_limits(&mut self) -> bool {
+        if self.tm.iteration_completed()
+            && (self.nodes >= self.node_limit || self.nodes & 2047 == 0 && self.tm.hard_limit_reached())
+        {
+            self.search_canceled = true;
+        }
+        self.search_canceled
+    }
+
     fn negamax(&mut self, mut alpha: Score, beta: Score, mut depth: i8, ply: u8, null_allowed: bool) -> Score {
+        if self.check_limits() {
+            return 0;
+        }
+
         let tt_index = self.tt.calc_index(self.board.key);
 
         if ply <= 1 {
             if self.board.key_history.contains_3fold() {
                 // self.tt.record(tt_index, self.board.key, Move::empty(), depth, 0, NodeType::Exact);
                 // self.board.key_history.print_history();
-                return 0;
+                return self.draw_score(ply);
             }
         } else {
             if self.board.key_history.contains_2fold() {
                 // self.tt.record(tt_index, self.board.key, Move::empty(), depth, 0, NodeType::Exact);
-                return 0;
+                return self.draw_score(ply);
             }
         }
 
         if depth <= 0 {
-            // Check for timeout on leaf node
-            if self.nodes & 2047 == 0 && self.timer.elapsed().as_millis() >= self.search_time {
-                self.search_canceled = true;
-                return 0;
-            }
-
             let score = self.quiescence(alpha, beta, ply);
             
             // TODO: SPRT uncommented when branching factor is lower
@@ -118,9 +200,10 @@ impl ChessEngine {
         }
 
         let mut best_move = match self.tt.probe(tt_index, alpha, beta, depth as u8, self.board.key) {
-            TTProbeResult::Score(score) => return score,
+            // A weakened engine needs the scores of all root moves, so the root is searched anyway
+            TTProbeResult::Score(score) if ply > 0 || !self.skill.enabled() => return score,
             TTProbeResult::BestMove(mv) => mv,
-            TTProbeResult::None => Move::empty()
+            TTProbeResult::Score(_) | TTProbeResult::None => Move::empty()
         };
 
         let mut moves = MoveList::new();
@@ -145,22 +228,30 @@ impl ChessEngine {
 
 
         if *moves.get_count() == 0 {
-            return if self.board.gs.is_in_check {-CHECKMATE_SCORE + ply as Score + 1} else {0};
+            return if self.board.gs.is_in_check {-CHECKMATE_SCORE + ply as Score + 1} else {self.draw_score(ply)};
         }
 
         let mut best_score = MIN_SCORE;
 
         for mv in moves.sort_with_grading_function(grade, best_move, &self.board) {
+            // A weakened engine chooses between the best root moves, so it needs their exact scores
+            let search_alpha = if ply == 0 && self.skill.enabled() {MIN_SCORE} else {alpha};
+
+            let nodes_before = self.nodes;
             self.board.make_move(&mv);
             self.nodes += 1;
             // TODO: SPRT null_allowed=true when reached depth is greater
-            let score = -self.negamax(-beta, -alpha, depth - 1, ply + 1, null_allowed);
+            let score = -self.negamax(-beta, -search_alpha, depth - 1, ply + 1, null_allowed);
             self.board.undo_move(&mv);
 
             if self.search_canceled {
                 return 0;
             }
 
+            if ply == 0 {
+                self.root_scores.push((mv, score));
+            }
+
             // println!("{} {}", mv, score);
 
             if score >= beta {
@@ -174,6 +265,10 @@ impl ChessEngine {
                     best_move = mv;
                     node_type = NodeType::Exact;
                     alpha = score;
+
+                    if ply == 0 {
+                        self.best_move_nodes = self.nodes - nodes_before; // used for time management
+                    }
                 }
             }
         }
@@ -189,11 +284,15 @@ impl ChessEngine {
     }
 
     fn quiescence(&mut self, mut alpha: Score, beta: Score, ply: u8) -> Score {
+        if self.check_limits() {
+            return 0;
+        }
+
         if ply > self.seldepth {
             self.seldepth = ply;
         }
 
-        let mut best_score = Eval::eval(&self.board) * self.board.gs.player_to_move as Score;
+        let mut best_score = self.eval.eval(&self.board, &self.mg) * self.board.gs.player_to_move as Score;
 
         let mut moves = MoveList::new();
         self.mg.generate_legal_moves(&mut self.board, &mut moves, true);
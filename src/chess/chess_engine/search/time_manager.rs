@@ -0,0 +1,224 @@
+use std::time::Instant;
+
+use super::super::{
+    SearchParams,
+    super::{Color, Move, Score}
+};
+
+// Assumed number of moves left in the game when the GUI does not send movestogo
+const DEFAULT_MOVES_TO_GO: u128 = 25;
+// The hard limit may exceed the soft limit by this factor, to finish a promising iteration
+const HARD_LIMIT_FACTOR: u128 = 4;
+
+// Soft limit scale, indexed by the number of consecutive iterations the best move did not change
+const STABILITY_SCALES: [f64; 5] = [2.5, 1.2, 0.9, 0.8, 0.75];
+// Score drop (in cp) at which the soft limit is doubled, and the score gain at which it is reduced by a quarter
+const SCORE_DROP_CRISIS: Score = 100;
+const SCORE_GAIN_CAP: Score = 25;
+// Soft limit scale is (NODES_SCALE_BASE - fraction of nodes spent on best move) * NODES_SCALE_FACTOR
+const NODES_SCALE_BASE: f64 = 1.5;
+const NODES_SCALE_FACTOR: f64 = 1.4;
+const MIN_SCALE: f64 = 0.3;
+const MAX_SCALE: f64 = 3.0;
+
+pub struct TimeManager {
+    timer: Instant,
+    soft_limit: u128,
+    hard_limit: u128,
+    iteration_completed: bool,
+
+    adaptive: bool,
+    scale: f64,
+    best_move: Move,
+    stability: usize,
+    previous_score: Score
+}
+
+impl TimeManager {
+    pub fn new() -> Self {
+        Self {
+            timer: Instant::now(),
+            soft_limit: u128::MAX,
+            hard_limit: u128::MAX,
+            iteration_completed: false,
+
+            adaptive: false,
+            scale: 1.0,
+            best_move: Move::empty(),
+            stability: 0,
+            previous_score: 0
+        }
+    }
+
+    /*
+        Soft limit: no new iteration is started after it has passed.
+        Hard limit: the search is aborted mid-iteration once it has passed.
+
+        Both limits never exceed the remaining time (minus the move overhead),
+        so a search can not flag, even if the last iteration takes very long.
+
+        When playing with a clock, the soft limit is scaled after every iteration (see iteration_finished).
+     */
+    pub fn start(&mut self, search_params: &SearchParams, color: Color) {
+        self.timer = Instant::now();
+        self.iteration_completed = false;
+        self.adaptive = search_params.move_time.is_none();
+        self.scale = 1.0;
+        self.best_move = Move::empty();
+        self.stability = 0;
+
+        (self.soft_limit, self.hard_limit) = if let Some(move_time) = search_params.move_time {
+            let move_time = move_time.saturating_sub(search_params.move_overhead);
+            (move_time, move_time)
+        } else {
+            let (time, inc) = match color {
+                Color::White => (search_params.wtime, search_params.winc),
+                Color::Black => (search_params.btime, search_params.binc),
+            };
+            let time_left = time.saturating_sub(search_params.move_overhead);
+            let max_time = time_left - time_left / 5; // always keep a reserve on the clock
+            let moves_to_go = search_params.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+
+            let soft_limit = std::cmp::min(time_left / moves_to_go + inc / 4 * 3, max_time);
+            let hard_limit = std::cmp::min(soft_limit.saturating_mul(HARD_LIMIT_FACTOR), max_time);
+            (soft_limit, hard_limit)
+        };
+    }
+
+    pub fn elapsed(&self) -> u128 {
+        self.timer.elapsed().as_millis()
+    }
+
+    /*
+        Scale the soft limit using:
+        - best move stability: stop early when the best move did not change for a few iterations
+        - score drop: think longer when the score drops compared to the previous iteration
+        - node fraction: stop early when most nodes were spent on the best move, as it clearly dominates
+     */
+    pub fn iteration_finished(&mut self, depth: u8, best_move: Move, score: Score, best_move_nodes: u64, nodes: u64) {
+        if depth == 1 {
+            self.previous_score = score;
+        }
+
+        if best_move == self.best_move {
+            self.stability = std::cmp::min(self.stability + 1, STABILITY_SCALES.len() - 1);
+        } else {
+            self.stability = 0;
+            self.best_move = best_move;
+        }
+        let stability_scale = STABILITY_SCALES[self.stability];
+
+        let score_drop = self.previous_score.saturating_sub(score).clamp(-SCORE_GAIN_CAP, SCORE_DROP_CRISIS);
+        let score_scale = 1.0 + score_drop as f64 / SCORE_DROP_CRISIS as f64;
+        self.previous_score = score;
+
+        let node_fraction = best_move_nodes as f64 / std::cmp::max(nodes, 1) as f64;
+        let nodes_scale = (NODES_SCALE_BASE - node_fraction) * NODES_SCALE_FACTOR;
+
+        self.scale = (stability_scale * score_scale * nodes_scale).clamp(MIN_SCALE, MAX_SCALE);
+        self.iteration_completed = true;
+    }
+
+    pub fn iteration_completed(&self) -> bool {
+        self.iteration_completed
+    }
+
+    pub fn soft_limit_reached(&self) -> bool {
+        let soft_limit = if self.adaptive {
+            (self.soft_limit as f64 * self.scale) as u128 // saturating cast
+        } else {
+            self.soft_limit
+        };
+        self.elapsed() >= soft_limit
+    }
+
+    pub fn hard_limit_reached(&self) -> bool {
+        self.elapsed() >= self.hard_limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock(wtime: u128, winc: u128) -> SearchParams {
+        let mut search_params = SearchParams::new();
+        search_params.wtime = wtime;
+        search_params.winc = winc;
+        search_params
+    }
+
+    #[test]
+    fn limits_from_clock() {
+        let mut tm = TimeManager::new();
+
+        // 10s for 10 moves: a tenth each, the hard limit at four times that
+        let mut search_params = clock(10_000, 0);
+        search_params.moves_to_go = Some(10);
+        tm.start(&search_params, Color::White);
+        assert_eq!((tm.soft_limit, tm.hard_limit), (1000, 4000));
+
+        // Without movestogo, 25 moves are assumed, and 3/4 of the increment is added
+        tm.start(&clock(10_000, 1000), Color::White);
+        assert_eq!((tm.soft_limit, tm.hard_limit), (1150, 4600));
+
+        // The move overhead is taken off the clock of the side to move, and a fifth is kept in reserve
+        let mut search_params = clock(0, 0);
+        (search_params.btime, search_params.move_overhead) = (1000, 200);
+        tm.start(&search_params, Color::Black);
+        assert_eq!((tm.soft_limit, tm.hard_limit), (32, 128));
+
+        // A large increment can't push the limits past the time on the clock
+        tm.start(&clock(100, 1000), Color::White);
+        assert_eq!((tm.soft_limit, tm.hard_limit), (80, 80));
+        assert!(tm.adaptive);
+    }
+
+    #[test]
+    fn limits_from_move_time() {
+        let mut tm = TimeManager::new();
+        let mut search_params = clock(10_000, 1000);
+        (search_params.move_time, search_params.move_overhead) = (Some(500), 100);
+        tm.start(&search_params, Color::White);
+        assert_eq!((tm.soft_limit, tm.hard_limit), (400, 400));
+        assert!(!tm.adaptive);
+    }
+
+    #[test]
+    fn scale_is_clamped() {
+        let mut tm = TimeManager::new();
+        tm.start(&clock(10_000, 0), Color::White);
+        let (a, b) = (Move::new(12, 28, &Move::empty()), Move::new(11, 27, &Move::empty()));
+
+        // A new best move with a collapsing score and few nodes on it would be 2.5 * 2 * 2.1, capped at MAX_SCALE
+        tm.iteration_finished(1, a, 0, 0, 1000);
+        tm.iteration_finished(2, b, 0, 0, 1000);
+        tm.iteration_finished(3, a, -1000, 0, 1000);
+        assert_eq!(tm.scale, MAX_SCALE);
+
+        // A stable best move with a rising score and all nodes on it: 0.75 * 0.75 * 0.7, the score gain capped at SCORE_GAIN_CAP
+        let mut tm = TimeManager::new();
+        tm.start(&clock(10_000, 0), Color::White);
+        for depth in 1..=6 {
+            tm.iteration_finished(depth, a, 500 * depth as Score, 1000, 1000);
+        }
+        assert!((tm.scale - 0.75 * 0.75 * 0.7).abs() < 1e-9);
+        assert!(tm.scale >= MIN_SCALE);
+    }
+
+    #[test]
+    fn score_drop_from_previous_iteration() {
+        let mut tm = TimeManager::new();
+        tm.start(&clock(10_000, 0), Color::White);
+        let a = Move::new(12, 28, &Move::empty());
+
+        // A drop of SCORE_DROP_CRISIS doubles the time, with a stable best move (1.2) and all nodes on it (0.7)
+        tm.iteration_finished(1, a, 0, 1000, 1000);
+        tm.iteration_finished(2, a, -SCORE_DROP_CRISIS, 1000, 1000);
+        assert!((tm.scale - 1.2 * 2.0 * 0.7).abs() < 1e-9);
+
+        // The score held at the next depth, so there is no crisis anymore
+        tm.iteration_finished(3, a, -SCORE_DROP_CRISIS, 1000, 1000);
+        assert!((tm.scale - 0.9 * 0.7).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,142 @@
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use super::super::{Move, Score};
+
+pub const MAX_SKILL_LEVEL: u8 = 20;
+pub const MIN_ELO: u16 = 600;
+pub const MAX_ELO: u16 = 2000;
+
+// Number of best root moves the weakened engine chooses from
+pub const SKILL_MULTI_PV: usize = 4;
+
+// Node limit at MIN_ELO, doubling every ELO_PER_DOUBLING elo
+const MIN_ELO_NODES: f64 = 64.0;
+const ELO_PER_DOUBLING: f64 = 100.0;
+
+/*
+    Strength limiting, used as a sparring partner:
+    - Skill Level: caps the search depth and picks a random move among the best SKILL_MULTI_PV root moves.
+      Lower levels make it more likely to pick a worse move.
+    - UCI_LimitStrength: the skill level is derived from UCI_Elo, and the number of nodes is capped as well.
+ */
+#[derive(Clone, Copy)]
+pub struct Skill {
+    pub level: u8,
+    pub limit_strength: bool,
+    pub elo: u16
+}
+
+impl Skill {
+    pub fn new() -> Self {
+        Self {
+            level: MAX_SKILL_LEVEL,
+            limit_strength: false,
+            elo: MAX_ELO
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.limit_strength || self.level < MAX_SKILL_LEVEL
+    }
+
+    fn effective_level(&self) -> u8 {
+        if self.limit_strength {
+            ((self.elo.clamp(MIN_ELO, MAX_ELO) - MIN_ELO) as u32 * MAX_SKILL_LEVEL as u32 / (MAX_ELO - MIN_ELO) as u32) as u8
+        } else {
+            self.level
+        }
+    }
+
+    pub fn depth_limit(&self) -> u8 {
+        let level = self.effective_level();
+        if level < MAX_SKILL_LEVEL {
+            level + 1
+        } else {
+            u8::MAX
+        }
+    }
+
+    pub fn node_limit(&self) -> u64 {
+        if self.limit_strength {
+            let elo = (self.elo.clamp(MIN_ELO, MAX_ELO) - MIN_ELO) as f64;
+            (MIN_ELO_NODES * 2f64.powf(elo / ELO_PER_DOUBLING)) as u64
+        } else {
+            u64::MAX
+        }
+    }
+
+    /*
+        Weighted random choice, based on Stockfish's Skill::pick_best:
+        every candidate gets a random push, which is larger for worse moves and lower levels.
+        The candidate with the highest pushed score is played.
+     */
+    pub fn pick_move(&self, candidates: &[(Move, Score)], rng: &mut ChaCha8Rng) -> Move {
+        let weakness = 120 - 2 * self.effective_level() as i32;
+        let top_score = candidates[0].1 as i32;
+        let delta = std::cmp::min(top_score - candidates[candidates.len() - 1].1 as i32, 100);
+
+        let mut best = candidates[0].0;
+        let mut max_score = i32::MIN;
+        for &(mv, score) in candidates {
+            let push = (weakness * (top_score - score as i32) + delta * rng.gen_range(0..weakness)) / 128;
+            if score as i32 + push >= max_score {
+                max_score = score as i32 + push;
+                best = mv;
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn limited(elo: u16) -> Skill {
+        Skill { level: MAX_SKILL_LEVEL, limit_strength: true, elo }
+    }
+
+    #[test]
+    fn elo_to_level() {
+        let levels = [(0, 0), (MIN_ELO, 0), (MIN_ELO + 69, 0), (MIN_ELO + 70, 1), (1300, 10), (MAX_ELO - 1, 19), (MAX_ELO, 20), (3000, 20)];
+        for (elo, level) in levels {
+            assert_eq!(limited(elo).effective_level(), level, "{}", elo);
+        }
+
+        // UCI_Elo only counts with UCI_LimitStrength
+        let skill = Skill { level: 7, limit_strength: false, elo: MIN_ELO };
+        assert_eq!((skill.effective_level(), skill.enabled()), (7, true));
+        assert!(!Skill::new().enabled());
+        assert!(limited(MAX_ELO).enabled());
+    }
+
+    #[test]
+    fn depth_and_node_limits() {
+        assert_eq!((limited(MIN_ELO).depth_limit(), limited(1300).depth_limit(), limited(MAX_ELO).depth_limit()), (1, 11, u8::MAX));
+        assert_eq!(Skill { level: 4, limit_strength: false, elo: MAX_ELO }.depth_limit(), 5);
+        assert_eq!(Skill::new().depth_limit(), u8::MAX);
+
+        // The node limit doubles every 100 elo
+        assert_eq!((limited(MIN_ELO).node_limit(), limited(MIN_ELO + 100).node_limit(), limited(MAX_ELO).node_limit()), (64, 128, 64 << 14));
+        assert_eq!(Skill { level: 0, limit_strength: false, elo: MIN_ELO }.node_limit(), u64::MAX);
+    }
+
+    #[test]
+    fn pick_move() {
+        let moves = [8, 9, 10, 11].map(|from| Move::new(from, from + 8, &Move::empty()));
+        let candidates = moves.iter().copied().zip([100, 60, 20, -200]).collect::<Vec<(Move, Score)>>();
+        let best_picks = |level: u8| {
+            let skill = Skill { level, limit_strength: false, elo: MAX_ELO };
+            let mut rng = ChaCha8Rng::seed_from_u64(3);
+            let picks = (0..1000).map(|_| skill.pick_move(&candidates, &mut rng)).collect::<Vec<Move>>();
+            picks.iter().filter(|&&mv| mv == moves[0]).count()
+        };
+
+        let (weakest, strongest) = (best_picks(0), best_picks(MAX_SKILL_LEVEL));
+        assert!(weakest < 500 && strongest > 500 && weakest < strongest, "{} {}", weakest, strongest);
+        assert_eq!(Skill::new().pick_move(&candidates[..1], &mut ChaCha8Rng::seed_from_u64(3)), moves[0]);
+    }
+}
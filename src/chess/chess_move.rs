@@ -5,6 +5,8 @@ use crate::Board;
 
 use super::{Square, precomputed, Color, PieceType::{self, *}};
 
+mod san;
+
 const SPECIAL_MOVE_NAMES: [&str; 16] = [
     "",
     ": double pawn push",
@@ -0,0 +1,171 @@
+use std::error::Error;
+
+use super::{
+    Move,
+    super::{Board, MoveGenerator, MoveList, Square, precomputed, util}
+};
+
+// Piece letters in SAN, indexed by piece type (white and black alike)
+const SAN_PIECES: &str = "PNBRQK";
+
+impl Move {
+    // Standard algebraic notation of a legal move, e.g. 'Nbd7', 'exd5', 'e8=Q+' or 'O-O#'
+    pub fn to_san(&self, board: &Board) -> String {
+        let mut board = Board::try_from_fen(&board.get_fen()).expect("Board should have a valid fen");
+        self.to_san_with(&mut board, MoveGenerator::shared())
+    }
+
+    // Same as to_san, without copying the board. The board is restored afterwards
+    pub fn to_san_with(&self, board: &mut Board, mg: &MoveGenerator) -> String {
+        let mut san = match self.intersection(Self::SPECIAL_BITS) {
+            Self::KING_CASTLE => String::from("O-O"),
+            Self::QUEEN_CASTLE => String::from("O-O-O"),
+            _ => self.san_without_suffix(board, mg)
+        };
+
+        board.make_move(self);
+        let mut replies = MoveList::new();
+        mg.generate_legal_moves(board, &mut replies, false);
+        if board.gs.is_in_check {
+            san.push(if *replies.get_count() == 0 {'#'} else {'+'});
+        }
+        board.undo_move(self);
+        san
+    }
+
+    fn san_without_suffix(&self, board: &mut Board, mg: &MoveGenerator) -> String {
+        let (from, to) = (self.get_from(), self.get_to());
+        let pt = board.piece_list[from as usize].expect("Moved piece should be on the board") as usize % 6;
+        let mut san = String::new();
+
+        if pt == 0 {
+            if self.is_capture() {
+                san.push(file_name(from));
+                san.push('x');
+            }
+            san.push_str(precomputed::SQUARE_NAMES[to as usize]);
+            if self.is_promotion() {
+                san.push('=');
+                san.push(util::piece_name_from_usize(self.get_promotion_piece(board.gs.player_to_move) as usize % 6));
+            }
+            return san;
+        }
+
+        san.push(util::piece_name_from_usize(pt));
+
+        // Other pieces of the same type that can move to the same square
+        let mut moves = MoveList::new();
+        mg.generate_legal_moves(board, &mut moves, false);
+        let others = moves
+            .filter(|mv| mv.get_to() == to && mv.get_from() != from)
+            .filter(|mv| board.piece_list[mv.get_from() as usize].is_some_and(|other| other as usize % 6 == pt))
+            .map(|mv| mv.get_from())
+            .collect::<Vec<_>>();
+        if !others.is_empty() {
+            if others.iter().all(|&sq| sq % 8 != from % 8) {
+                san.push(file_name(from));
+            } else if others.iter().all(|&sq| sq / 8 != from / 8) {
+                san.push(rank_name(from));
+            } else {
+                san.push_str(precomputed::SQUARE_NAMES[from as usize]);
+            }
+        }
+
+        if self.is_capture() {
+            san.push('x');
+        }
+        san.push_str(precomputed::SQUARE_NAMES[to as usize]);
+        san
+    }
+
+    /*
+        Parses a move in standard algebraic notation. Also accepts common variants:
+        missing or superfluous captures and disambiguation ('ed5', 'Ng1f3', 'Ng1-f3'), promotions without '=' ('e8Q'),
+        castling with zeros ('0-0'), and check and annotation suffixes ('+', '#', '!', '?').
+     */
+    pub fn from_san(san: &str, board: &Board) -> Result<Self, Box<dyn Error>> {
+        let mut board = Board::try_from_fen(&board.get_fen())?;
+        Self::from_san_with(san, &mut board, MoveGenerator::shared())
+    }
+
+    pub fn from_san_with(san: &str, board: &mut Board, mg: &MoveGenerator) -> Result<Self, Box<dyn Error>> {
+        let mut moves = MoveList::new();
+        mg.generate_legal_moves(board, &mut moves, false);
+
+        let stripped = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        let castle = match stripped {
+            "O-O" | "0-0" => Some(Self::KING_CASTLE),
+            "O-O-O" | "0-0-0" => Some(Self::QUEEN_CASTLE),
+            _ => None
+        };
+        if let Some(castle) = castle {
+            return moves.into_iter()
+                .find(|mv| mv.intersection(Self::SPECIAL_BITS) == castle)
+                .ok_or(format!("Illegal move: {}", san).into());
+        }
+
+        let mut chars = stripped.chars().filter(|&c| c != 'x' && c != '-' && c != ':').collect::<Vec<char>>();
+
+        // Promotion piece, with or without '='
+        let promotion = match chars.last() {
+            Some(c) if chars.len() > 2 && "NBRQnbrq".contains(*c) => {
+                let pt = SAN_PIECES.find(c.to_ascii_uppercase()).expect("Promotion piece should be a piece");
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+                Some(pt)
+            },
+            _ => None
+        };
+
+        let pt = match chars.first() {
+            Some(c) if c.is_ascii_uppercase() => {
+                let pt = SAN_PIECES.find(*c).ok_or(format!("Invalid piece in move: {}", san))?;
+                chars.remove(0);
+                pt
+            },
+            _ => 0
+        };
+
+        if chars.len() < 2 {
+            return Err(format!("Invalid move: {}", san).into());
+        }
+        let to_name = chars.split_off(chars.len() - 2).into_iter().collect::<String>();
+        let to = precomputed::SQUARE_NAMES.iter().position(|name| *name == to_name).ok_or(format!("Invalid square in move: {}", san))?;
+
+        // Whatever is left is disambiguation
+        let from_file = chars.iter().find(|c| ('a'..='h').contains(c)).map(|c| *c as u8 - b'a');
+        let from_rank = chars.iter().find(|c| ('1'..='8').contains(c)).map(|c| *c as u8 - b'1');
+        if chars.len() > 2 || chars.iter().any(|c| !('a'..='h').contains(c) && !('1'..='8').contains(c)) {
+            return Err(format!("Invalid move: {}", san).into());
+        }
+
+        let color = board.gs.player_to_move;
+        let mut candidates = moves.filter(|mv| {
+            let from = mv.get_from();
+            mv.get_to() as usize == to
+                && board.piece_list[from as usize].is_some_and(|piece| piece as usize % 6 == pt)
+                && from_file.is_none_or(|file| from % 8 == file)
+                && from_rank.is_none_or(|rank| from / 8 == rank)
+                && match promotion {
+                    Some(promotion) => mv.is_promotion() && mv.get_promotion_piece(color) as usize % 6 == promotion,
+                    None => !mv.is_promotion()
+                }
+        });
+
+        match (candidates.next(), candidates.next()) {
+            (Some(mv), None) => Ok(mv),
+            (None, _) => Err(format!("Illegal move: {}", san).into()),
+            (Some(_), Some(_)) => Err(format!("Ambiguous move: {}", san).into())
+        }
+    }
+}
+
+fn file_name(sq: Square) -> char {
+    (b'a' + sq % 8) as char
+}
+
+fn rank_name(sq: Square) -> char {
+    (b'1' + sq / 8) as char
+}
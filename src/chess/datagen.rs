@@ -0,0 +1,182 @@
+use std::{error::Error, sync::{atomic::{AtomicUsize, Ordering}, mpsc}, thread, time::Instant};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::{
+    ChessEngine, SearchParams, Board, Move, Score, Color, PieceType::*, PackedPosition, PackedWriter, CHECKMATE_SCORE,
+    RESULT_BLACK_WIN, RESULT_DRAW, RESULT_WHITE_WIN, precomputed
+};
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const DATAGEN_TABLE_SIZE: usize = 16;
+
+// Openings that are already decided after the random plies are thrown away
+const MAX_OPENING_SCORE: Score = 400;
+// A game is adjudicated as won once the score stays above this for WIN_ADJUDICATION_PLIES plies
+const WIN_ADJUDICATION_SCORE: Score = 2000;
+const WIN_ADJUDICATION_PLIES: usize = 6;
+// And as a draw once the score stays close to zero for long enough, late in the game
+const DRAW_ADJUDICATION_SCORE: Score = 10;
+const DRAW_ADJUDICATION_PLIES: usize = 12;
+const DRAW_ADJUDICATION_MIN_PLY: usize = 80;
+// Games that last longer, random plies included, are drawn. The board keeps the state of at most 512 plies,
+// which also have to fit the plies of the search (up to MAX_DEPTH, and quiescence on top of that).
+// This is the default and the upper bound of Datagen::max_plies
+const MAX_GAME_PLIES: usize = 400;
+
+const PROGRESS_INTERVAL: usize = 100;
+
+/*
+    Plays self-play games from randomised openings and writes the quiet positions with their search score and
+    the final game result as packed positions. Every game has its own seed, so a run is reproducible
+    independent of the number of threads (apart from the order of the games in the output).
+ */
+pub struct Datagen {
+    pub games: usize,
+    pub threads: usize,
+    pub nodes: u64,
+    pub depth: u8,
+    pub random_plies: usize,
+    pub max_plies: usize,
+    pub seed: u64
+}
+
+impl Datagen {
+    pub fn new() -> Self {
+        Self {
+            games: 1000,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            nodes: 5000,
+            depth: u8::MAX,
+            random_plies: 8,
+            max_plies: MAX_GAME_PLIES,
+            seed: 0
+        }
+    }
+
+    // Appends to output, and returns the number of games and positions written
+    pub fn run(&self, output: &str) -> Result<(usize, usize), Box<dyn Error>> {
+        let mut writer = PackedWriter::append(output)?;
+        let next_game = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel::<Vec<PackedPosition>>();
+        let start = Instant::now();
+
+        thread::scope(|s| {
+            for _ in 0..self.threads.max(1) {
+                let sender = sender.clone();
+                let next_game = &next_game;
+                s.spawn(move || {
+                    let mut engine = ChessEngine::new(START_FEN, DATAGEN_TABLE_SIZE);
+                    loop {
+                        let game = next_game.fetch_add(1, Ordering::Relaxed);
+                        if game >= self.games || sender.send(self.play_game(&mut engine, self.seed.wrapping_add(game as u64))).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            // Dropping the receiver on an error stops the game threads
+            let mut games = 0;
+            let mut positions = 0;
+            for game in receiver {
+                for position in &game {
+                    writer.write(position)?;
+                }
+                games += 1;
+                positions += game.len();
+                if games % PROGRESS_INTERVAL == 0 || games == self.games {
+                    let elapsed = start.elapsed().as_secs_f32();
+                    println!("{}/{} games | {} positions | {:.0} pos/s", games, self.games, positions, positions as f32 / elapsed.max(1e-3));
+                }
+            }
+            writer.flush()?;
+            Ok((games, positions))
+        })
+    }
+
+    // The recorded positions of a single game, empty if the opening was thrown away
+    fn play_game(&self, engine: &mut ChessEngine, seed: u64) -> Vec<PackedPosition> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        engine.set_board(START_FEN).expect("Start fen should be valid");
+        engine.reset_table(DATAGEN_TABLE_SIZE);
+
+        let max_plies = self.max_plies.min(MAX_GAME_PLIES);
+        let random_plies = self.random_plies.min(max_plies);
+        for _ in 0..random_plies {
+            let moves = engine.legal_moves().collect::<Vec<Move>>();
+            if moves.is_empty() {
+                return Vec::new();
+            }
+            engine.make_move(moves[rng.gen_range(0..moves.len())]);
+        }
+
+        let mut positions = Vec::new();
+        let mut win_plies = 0;
+        let mut draw_plies = 0;
+        let result = 'game: {
+            for ply in 0..max_plies - random_plies {
+                let moves = engine.legal_moves();
+                let board = engine.get_board();
+                let us = board.gs.player_to_move;
+                let in_check = board.gs.is_in_check;
+                if *moves.get_count() == 0 {
+                    break 'game if !in_check {
+                        RESULT_DRAW
+                    } else if us == Color::White {
+                        RESULT_BLACK_WIN
+                    } else {
+                        RESULT_WHITE_WIN
+                    };
+                }
+                if board.key_history.contains_3fold() || board.key_history.halfmove_clock() >= 100 || insufficient_material(board) {
+                    break 'game RESULT_DRAW;
+                }
+
+                let mut search_params = SearchParams::new();
+                search_params.nodes = self.nodes;
+                search_params.depth = self.depth;
+                let (mv, score) = engine.search(search_params, false);
+                let board = engine.get_board();
+                let white_score = score * us as Score;
+                if ply == 0 && score.abs() > MAX_OPENING_SCORE {
+                    return Vec::new();
+                }
+
+                win_plies = if white_score.abs() >= WIN_ADJUDICATION_SCORE { win_plies + 1 } else { 0 };
+                if win_plies >= WIN_ADJUDICATION_PLIES {
+                    break 'game if white_score > 0 { RESULT_WHITE_WIN } else { RESULT_BLACK_WIN };
+                }
+                draw_plies = if white_score.abs() <= DRAW_ADJUDICATION_SCORE { draw_plies + 1 } else { 0 };
+                if draw_plies >= DRAW_ADJUDICATION_PLIES && ply >= DRAW_ADJUDICATION_MIN_PLY {
+                    break 'game RESULT_DRAW;
+                }
+
+                // Mate scores depend on the distance to mate, which the eval can't learn
+                if !in_check && !mv.is_capture() && !mv.is_promotion() && CHECKMATE_SCORE - score.abs() >= 100 {
+                    positions.push(PackedPosition::new(board, white_score, RESULT_DRAW));
+                }
+                engine.make_move(mv);
+            }
+            RESULT_DRAW
+        };
+
+        positions.iter_mut().for_each(|position| position.result = result);
+        positions
+    }
+}
+
+impl Default for Datagen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Neither side can mate with only a king and at most one minor piece
+fn insufficient_material(board: &Board) -> bool {
+    let majors_and_pawns = [WPawn, WRook, WQueen, BPawn, BRook, BQueen].iter().any(|&pt| board.bbs[pt as usize] != precomputed::EMPTY);
+    let minors = [WKnight, WBishop, BKnight, BBishop].iter().map(|&pt| board.bbs[pt as usize].count_ones()).sum::<u32>();
+    !majors_and_pawns && minors <= 1
+}
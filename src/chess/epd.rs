@@ -0,0 +1,153 @@
+use std::{error::Error, fs::File, io::{BufRead, BufReader}};
+
+use super::{Board, Move, MoveGenerator, MoveList};
+
+// An opcode with its operands
+type Operation = (String, Vec<String>);
+
+/*
+    A position in Extended Position Description: the first four FEN fields, followed by operations like
+    'bm Qg6; id "WAC.001";'. The operations are kept in order, and the common ones are also parsed:
+    bm (best moves), am (avoid moves), id, c0 (comment), dm (direct mate in n moves) and acd (analysis depth).
+ */
+#[derive(Clone, Debug)]
+pub struct EpdPosition {
+    // Full FEN, with the move counters from hmvc and fmvn
+    pub fen: String,
+    pub operations: Vec<Operation>,
+    pub best_moves: Vec<Move>,
+    pub avoid_moves: Vec<Move>,
+    pub id: Option<String>,
+    pub comment: Option<String>,
+    pub direct_mate: Option<u32>,
+    pub depth: Option<u8>
+}
+
+impl EpdPosition {
+    pub fn parse(line: &str) -> Result<Self, Box<dyn Error>> {
+        let line = line.trim();
+        let fields = line.split_ascii_whitespace().take(4).collect::<Vec<&str>>();
+        if fields.len() < 4 {
+            return Err(format!("Not enough fields in EPD: {}", line).into());
+        }
+        let position = fields.join(" ");
+        let operations = parse_operations(line.splitn(5, |c: char| c.is_ascii_whitespace()).nth(4).unwrap_or(""))?;
+
+        let operand = |opcode: &str| operations.iter().find(|(op, _)| op == opcode).and_then(|(_, operands)| operands.first());
+        let halfmove_clock = operand("hmvc").map_or(Ok(0), |n| n.parse::<u32>())?;
+        let fullmove_number = operand("fmvn").map_or(Ok(1), |n| n.parse::<u32>())?;
+        let fen = format!("{} {} {}", position, halfmove_clock, fullmove_number);
+
+        let mut board = Board::try_from_fen(&fen)?;
+        let mut moves = |opcode: &str| -> Result<Vec<Move>, Box<dyn Error>> {
+            operations.iter()
+                .filter(|(op, _)| op == opcode)
+                .flat_map(|(_, operands)| operands)
+                .map(|name| parse_move(name, &mut board).map_err(|e| format!("{} {}: {}", opcode, name, e).into()))
+                .collect()
+        };
+        let best_moves = moves("bm")?;
+        let avoid_moves = moves("am")?;
+
+        let id = operand("id").cloned();
+        let comment = operand("c0").cloned();
+        let direct_mate = operand("dm").map(|n| n.parse()).transpose()?;
+        let depth = operand("acd").map(|n| n.parse()).transpose()?;
+
+        Ok(Self { fen, operations, best_moves, avoid_moves, id, comment, direct_mate, depth })
+    }
+
+    // Reads all positions of a file, skipping empty and invalid lines
+    pub fn load(path: &str) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut positions = Vec::new();
+        let mut skipped = 0;
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match Self::parse(&line) {
+                Ok(position) => positions.push(position),
+                Err(_) => skipped += 1
+            }
+        }
+
+        if skipped > 0 {
+            println!("Skipped {} invalid lines", skipped);
+        }
+        if positions.is_empty() {
+            return Err("No positions found".into());
+        }
+        Ok(positions)
+    }
+
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|(op, _)| op == opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    pub fn board(&self) -> Board {
+        Board::try_from_fen(&self.fen).expect("Parsed EPD should have a valid fen")
+    }
+}
+
+// Operations end with ';'. Operands are separated by spaces, or quoted strings that may contain spaces and ';'
+fn parse_operations(text: &str) -> Result<Vec<Operation>, Box<dyn Error>> {
+    let mut operations = Vec::new();
+    let mut words = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            ';' => {
+                chars.next();
+                if !words.is_empty() {
+                    let opcode = words.remove(0);
+                    operations.push((opcode, std::mem::take(&mut words)));
+                }
+            },
+            '"' => {
+                chars.next();
+                let mut word = String::new();
+                loop {
+                    match chars.next().ok_or("Unterminated string in EPD")? {
+                        '"' => break,
+                        '\\' => word.push(chars.next().ok_or("Unterminated string in EPD")?),
+                        c => word.push(c)
+                    }
+                }
+                words.push(word);
+            },
+            c if c.is_whitespace() => {
+                chars.next();
+            },
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == ';' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                words.push(word);
+            }
+        }
+    }
+
+    // The last operation may lack its ';'
+    if !words.is_empty() {
+        let opcode = words.remove(0);
+        operations.push((opcode, words));
+    }
+    Ok(operations)
+}
+
+// Moves are in SAN, but some suites use coordinate notation
+fn parse_move(name: &str, board: &mut Board) -> Result<Move, Box<dyn Error>> {
+    let mg = MoveGenerator::shared();
+    Move::from_san_with(name, board, mg).or_else(|e| {
+        let mut moves = MoveList::new();
+        mg.generate_legal_moves(board, &mut moves, false);
+        moves.find(|mv| mv.to_string() == name).ok_or(e)
+    })
+}
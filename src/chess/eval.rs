@@ -1,40 +1,144 @@
-use super::{Board, Score, precomputed, util};
+use std::sync::Arc;
+
+use super::{Board, MoveGenerator, Score};
 
 mod piece_square;
-use piece_square::{MG_PIECE_SQUARE_TABLES, EG_PIECE_SQUARE_TABLES, GAME_PHASE_INCREMENT, MAX_GAME_PHASE};
+pub use piece_square::{GAME_PHASE_INCREMENT, MAX_GAME_PHASE};
+
+mod params;
+pub use params::{EvalParams, default_params};
+
+mod wdl;
+pub use wdl::{WdlModel, WdlSample};
+
+mod tapered_score;
+pub use tapered_score::TaperedScore;
+
+mod pawn_table;
+use pawn_table::{PawnHashTable, PawnEntry};
+
+mod attacks;
+use attacks::AttackMaps;
+
+mod pawns;
+mod passed_pawns;
+mod pieces;
+mod king_safety;
+mod threats;
+mod endgame;
+use endgame::SCALE_FACTOR_NORMAL;
+
+mod trace;
+pub use trace::EvalTrace;
+use trace::Tracer;
+
+mod tuner;
+pub use tuner::Tuner;
+
+pub mod nnue;
 
-pub struct Eval;
+mod breakdown;
+pub use breakdown::{EvalBreakdown, EvalTerm};
+
+// The handcrafted eval. Boards it evaluates have to use the same weights for their incremental piece-square eval
+pub struct Eval {
+    pawn_table: PawnHashTable,
+    params: Arc<EvalParams>,
+    // Replaces the handcrafted eval while it is set
+    #[cfg(feature = "nnue")]
+    network: Option<Arc<nnue::Network>>
+}
 
 impl Eval {
+    pub fn new() -> Self {
+        Self::with_params(default_params())
+    }
+
+    pub fn with_params(params: Arc<EvalParams>) -> Self {
+        Self {
+            pawn_table: PawnHashTable::new(),
+            params,
+            #[cfg(feature = "nnue")]
+            network: None
+        }
+    }
+
+    pub fn params(&self) -> &Arc<EvalParams> {
+        &self.params
+    }
+
+    // Also clears the pawn table, which was computed with the old weights
+    pub fn set_params(&mut self, params: Arc<EvalParams>) {
+        self.params = params;
+        self.pawn_table.clear();
+    }
+
+    #[cfg(feature = "nnue")]
+    pub fn network(&self) -> Option<&Arc<nnue::Network>> {
+        self.network.as_ref()
+    }
+
+    // None goes back to the handcrafted eval
+    #[cfg(feature = "nnue")]
+    pub fn set_network(&mut self, network: Option<Arc<nnue::Network>>) {
+        self.network = network;
+    }
+
+    // Ranges from 0 (endgame) to MAX_GAME_PHASE (opening)
+    pub fn game_phase(board: &Board) -> i32 {
+        std::cmp::min(board.game_phase, MAX_GAME_PHASE)
+    }
+
     // #[inline(always)]
-    pub fn eval(board: &Board) -> Score {
-        let mut mg = 0;
-        let mut eg = 0;
-        let mut mg_game_phase = 0;
-
-        for pt in 0..12 {
-            let mut pieces = board.bbs[pt];
-            while pieces != precomputed::EMPTY {
-                let sq = util::pop_ls1b(&mut pieces);
-                // if verbose {
-                //     println!("{} on {}", util::piece_name_from_usize(pt), precomputed::SQUARE_NAMES[sq as usize]);
-                //     println!("mg: {}", MG_PIECE_SQUARE_TABLES[pt][sq as usize]);
-                //     println!("eg: {}", EG_PIECE_SQUARE_TABLES[pt][sq as usize]);
-                //     println!("phase: {}", GAME_PHASE_INCREMENT[pt]);
-                // }
-                mg += MG_PIECE_SQUARE_TABLES[pt][sq as usize];
-                eg += EG_PIECE_SQUARE_TABLES[pt][sq as usize];
-                mg_game_phase += GAME_PHASE_INCREMENT[pt];
-            }
+    pub fn eval(&mut self, board: &Board, mg: &MoveGenerator) -> Score {
+        #[cfg(feature = "nnue")]
+        if let Some(network) = &self.network {
+            debug_assert!(board.network().is_some_and(|board_network| Arc::ptr_eq(board_network, network)));
+            debug_assert!(board.accumulator == nnue::Accumulator::refresh(network, board));
+            return network.evaluate(&board.accumulator, board.gs.player_to_move) * board.gs.player_to_move as Score;
         }
+        self.evaluate(board, mg, &mut ())
+    }
 
-        if mg_game_phase > MAX_GAME_PHASE {
-            mg_game_phase = MAX_GAME_PHASE
+    // The eval as a linear function of the weights, used for tuning
+    pub fn trace(&mut self, board: &Board, mg: &MoveGenerator) -> EvalTrace {
+        let mut trace = EvalTrace::new();
+        self.evaluate(board, mg, &mut trace);
+        // The endgame evaluators are not tapered
+        trace.phase = if trace.endgame_evaluator {0} else {Self::game_phase(board)};
+        trace
+    }
+
+    fn evaluate<T: Tracer>(&mut self, board: &Board, mg: &MoveGenerator, trace: &mut T) -> Score {
+        // The piece-square terms are updated incrementally with the weights of the board (checked in perft)
+        debug_assert!(Arc::ptr_eq(board.eval_params(), &self.params));
+
+        if let Some(score) = self.evaluate_endgame(board, trace) {
+            trace.set_endgame_evaluator();
+            trace.set_scale_factor(SCALE_FACTOR_NORMAL);
+            return score;
         }
-        let eg_game_phase = MAX_GAME_PHASE - mg_game_phase;
-        // if verbose {
-        //     println!("MG: {} | EG: {} | phase: {}/{}", mg, eg, mg_game_phase, MAX_GAME_PHASE);
-        // }
-        ((mg_game_phase * mg as i32 + eg_game_phase * eg as i32) / MAX_GAME_PHASE) as Score
+
+        let mut score = TaperedScore::new(board.mg_score as i32, board.eg_score as i32);
+        if T::ENABLED {
+            Self::trace_psqt(board, trace);
+        }
+        let pawn_entry = self.evaluate_pawns(board, trace);
+        score += pawn_entry.score;
+
+        let attacks = AttackMaps::new(board, mg);
+        score += self.evaluate_passed_pawns(board, &attacks, pawn_entry.passed_pawns, trace);
+        score += self.evaluate_pieces(board, &attacks, trace);
+        score += self.evaluate_king_safety(board, &attacks, trace);
+        score += self.evaluate_threats(board, &attacks, trace);
+        trace.set_scale_factor(Self::apply_scale_factor(&mut score, board));
+
+        score.taper(Self::game_phase(board))
+    }
+}
+
+impl Default for Eval {
+    fn default() -> Self {
+        Self::new()
     }
 }
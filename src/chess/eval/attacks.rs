@@ -0,0 +1,87 @@
+use super::super::{Board, Bitboard, Square, Color, MoveGenerator, PieceType::{self, *}, precomputed, util};
+
+// Attack maps of both sides, computed once per eval and shared by all eval terms
+pub struct AttackMaps {
+    // Indexed by the square of the attacking piece
+    piece_attacks: [Bitboard; 64],
+    // Indexed by PieceType, AnyWhite and AnyBlack contain all attacks of that color
+    by_piece_type: [Bitboard; 14],
+    // Squares attacked at least twice, by white and black
+    double_attacks: [Bitboard; 2]
+}
+
+impl AttackMaps {
+    pub fn new(board: &Board, mg: &MoveGenerator) -> Self {
+        let mut attack_maps = Self {
+            piece_attacks: [precomputed::EMPTY; 64],
+            by_piece_type: [precomputed::EMPTY; 14],
+            double_attacks: [precomputed::EMPTY; 2]
+        };
+        attack_maps.add_attacks(board, mg, Color::White);
+        attack_maps.add_attacks(board, mg, Color::Black);
+        attack_maps
+    }
+
+    fn add_attacks(&mut self, board: &Board, mg: &MoveGenerator, color: Color) {
+        let occupied = board.bbs[AnyPiece as usize];
+        let any = PieceType::from_color(AnyWhite, color) as usize;
+
+        for pt in [WPawn, WKnight, WBishop, WRook, WQueen, WKing] {
+            let pt = PieceType::from_color(pt, color);
+            let mut pieces = board.bbs[pt as usize];
+
+            if pt == WPawn || pt == BPawn {
+                let attacks = util::pawn_attacks(pieces, color);
+                self.by_piece_type[pt as usize] = attacks;
+                self.by_piece_type[any] |= attacks;
+                continue;
+            }
+
+            while pieces != precomputed::EMPTY {
+                let sq = util::pop_ls1b(&mut pieces);
+                let attacks = match pt {
+                    WKnight | BKnight => precomputed::KNIGHT_MOVES[sq as usize],
+                    WBishop | BBishop => mg.get_bishop_attacks(occupied, sq),
+                    WRook | BRook => mg.get_rook_attacks(occupied, sq),
+                    WQueen | BQueen => mg.get_rook_attacks(occupied, sq) | mg.get_bishop_attacks(occupied, sq),
+                    _ => precomputed::KING_MOVES[sq as usize]
+                };
+
+                self.piece_attacks[sq as usize] = attacks;
+                self.by_piece_type[pt as usize] |= attacks;
+                self.double_attacks[Self::color_index(color)] |= self.by_piece_type[any] & attacks;
+                self.by_piece_type[any] |= attacks;
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn color_index(color: Color) -> usize {
+        match color {
+            Color::White => 0,
+            Color::Black => 1
+        }
+    }
+
+    // Attacks of the (non-pawn) piece on the given square
+    #[inline(always)]
+    pub fn piece(&self, sq: Square) -> Bitboard {
+        self.piece_attacks[sq as usize]
+    }
+
+    // Attacks of all pieces of the given type, PieceType::AnyWhite and PieceType::AnyBlack give all attacks of that color
+    #[inline(always)]
+    pub fn by_piece_type(&self, pt: PieceType) -> Bitboard {
+        self.by_piece_type[pt as usize]
+    }
+
+    #[inline(always)]
+    pub fn by_color(&self, color: Color) -> Bitboard {
+        self.by_piece_type[PieceType::from_color(AnyWhite, color) as usize]
+    }
+
+    #[inline(always)]
+    pub fn double(&self, color: Color) -> Bitboard {
+        self.double_attacks[Self::color_index(color)]
+    }
+}
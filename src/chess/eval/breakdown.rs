@@ -0,0 +1,115 @@
+use std::fmt;
+
+use super::{
+    Eval, EvalParams, EvalTrace, TaperedScore,
+    endgame::SCALE_FACTOR_NORMAL,
+    piece_square::MAX_GAME_PHASE,
+    super::{Board, MoveGenerator, Score, Color}
+};
+
+// The weights of a term, taken from either the eval weights or the traced counts
+type TermWeights = fn(&EvalParams) -> Vec<TaperedScore>;
+
+const TERMS: [(&str, TermWeights); 13] = [
+    ("Material",       |p| p.piece_values.to_vec()),
+    ("Pawn PST",       |p| p.piece_square_tables[0].to_vec()),
+    ("Knight PST",     |p| p.piece_square_tables[1].to_vec()),
+    ("Bishop PST",     |p| p.piece_square_tables[2].to_vec()),
+    ("Rook PST",       |p| p.piece_square_tables[3].to_vec()),
+    ("Queen PST",      |p| p.piece_square_tables[4].to_vec()),
+    ("King PST",       |p| p.piece_square_tables[5].to_vec()),
+    ("Pawn structure", |p| [&[p.doubled_pawn, p.isolated_pawn, p.backward_pawn, p.pawn_island], p.connected_pawn.as_slice()].concat()),
+    ("Passed pawns",   |p| [
+        p.passed_pawn.as_slice(), &p.passed_pawn_free_path, &p.passed_pawn_safe_path,
+        &p.passed_pawn_enemy_king_distance, &p.passed_pawn_own_king_distance, &[p.unstoppable_passed_pawn]
+    ].concat()),
+    ("Mobility",       |p| [p.knight_mobility.as_slice(), &p.bishop_mobility, &p.rook_mobility, &p.queen_mobility].concat()),
+    ("Pieces",         |p| vec![p.rook_open_file, p.rook_semi_open_file, p.rook_on_seventh, p.knight_outpost, p.bishop_pair]),
+    ("Threats",        |p| [&[p.threat_by_pawn, p.threat_by_rook, p.hanging_piece, p.pawn_push_threat], p.threat_by_minor.as_slice()].concat()),
+    ("King safety",    |p| p.safety_table.to_vec()),
+];
+
+// The contribution of a single eval term, each from the perspective of its own color
+#[derive(Clone, Copy, Debug)]
+pub struct EvalTerm {
+    pub name: &'static str,
+    pub white: TaperedScore,
+    pub black: TaperedScore
+}
+
+impl EvalTerm {
+    // From white's perspective
+    pub fn total(&self) -> TaperedScore {
+        self.white - self.black
+    }
+}
+
+/*
+    The static eval split up into its terms. Positions that are scored by a dedicated endgame evaluator have no terms.
+    The scale factor only applies to the endgame part of the total.
+ */
+#[derive(Clone, Debug)]
+pub struct EvalBreakdown {
+    pub terms: Vec<EvalTerm>,
+    pub phase: i32,
+    pub scale_factor: i32,
+    pub endgame_evaluator: bool,
+    // The final eval from white's perspective
+    pub score: Score
+}
+
+impl Eval {
+    pub fn breakdown(&mut self, board: &Board, mg: &MoveGenerator) -> EvalBreakdown {
+        // The handcrafted eval, also when a network is loaded
+        let score = self.evaluate(board, mg, &mut ());
+        let trace = self.trace(board, mg);
+        if trace.endgame_evaluator {
+            return EvalBreakdown { terms: Vec::new(), phase: trace.phase, scale_factor: SCALE_FACTOR_NORMAL, endgame_evaluator: true, score };
+        }
+
+        let p = &*self.params;
+        let term_score = |color: Color, weights: TermWeights| {
+            weights(p).iter().zip(weights(&trace.counts[EvalTrace::index(color)]))
+                .fold(TaperedScore::ZERO, |acc, (weight, count)| acc + *weight * count.mg)
+        };
+        let terms = TERMS.iter()
+            .map(|&(name, weights)| EvalTerm { name, white: term_score(Color::White, weights), black: term_score(Color::Black, weights) })
+            .collect::<Vec<EvalTerm>>();
+
+        EvalBreakdown { terms, phase: trace.phase, scale_factor: trace.scale_factor, endgame_evaluator: false, score }
+    }
+}
+
+impl EvalBreakdown {
+    // The sum of all terms from white's perspective, before scaling
+    pub fn total(&self) -> TaperedScore {
+        self.terms.iter().fold(TaperedScore::ZERO, |acc, term| acc + term.total())
+    }
+}
+
+impl fmt::Display for EvalBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.endgame_evaluator {
+            writeln!(f, "Scored by a specialized endgame evaluator")?;
+            return write!(f, "Final evaluation: {} (white side)", self.score);
+        }
+
+        writeln!(f, "      Term      |    White    |    Black    |    Total")?;
+        writeln!(f, "                |   MG    EG  |   MG    EG  |   MG    EG")?;
+        writeln!(f, "----------------+-------------+-------------+------------")?;
+        let row = |f: &mut fmt::Formatter<'_>, name: &str, white: TaperedScore, black: TaperedScore, total: TaperedScore| {
+            writeln!(f, "{:>15} | {:>5} {:>5} | {:>5} {:>5} | {:>5} {:>5}", name, white.mg, white.eg, black.mg, black.eg, total.mg, total.eg)
+        };
+        for term in &self.terms {
+            row(f, term.name, term.white, term.black, term.total())?;
+        }
+        writeln!(f, "----------------+-------------+-------------+------------")?;
+        let white = self.terms.iter().fold(TaperedScore::ZERO, |acc, term| acc + term.white);
+        let black = self.terms.iter().fold(TaperedScore::ZERO, |acc, term| acc + term.black);
+        row(f, "Total", white, black, self.total())?;
+
+        writeln!(f)?;
+        writeln!(f, "Phase: {}/{} | Scale factor: {}/{}", self.phase, MAX_GAME_PHASE, self.scale_factor, SCALE_FACTOR_NORMAL)?;
+        write!(f, "Final evaluation: {} (white side)", self.score)
+    }
+}
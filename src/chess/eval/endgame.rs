@@ -0,0 +1,217 @@
+use super::{
+    Eval, TaperedScore, Tracer,
+    piece_square::GAME_PHASE_INCREMENT,
+    super::{Board, Bitboard, Square, Score, Color, PieceType::{self, *}, precomputed, util}
+};
+
+// Scale factors for the endgame part of the eval
+pub(super) const SCALE_FACTOR_NORMAL: i32 = 64;
+const SCALE_FACTOR_DRAW: i32 = 0;
+const SCALE_FACTOR_OPPOSITE_BISHOPS: i32 = 22; // only bishops and pawns left
+const SCALE_FACTOR_OPPOSITE_BISHOPS_WITH_PIECES: i32 = 48;
+const SCALE_FACTOR_PAWNLESS_MINOR_UP: i32 = 8;
+
+// A score that is clearly winning, but far away from mate scores
+const KNOWN_WIN: i32 = 1000;
+
+// Mop-up weights for driving the lone king
+const PUSH_TO_EDGE: i32 = 20;
+const PUSH_TO_CORNER: i32 = 40;
+const PUSH_CLOSE: i32 = 10;
+
+impl Eval {
+    /*
+        Dedicated evaluation of endgames where the general eval is of little help.
+        Returns the score from white's perspective, or None if the material signature is not recognised.
+        The scores are not tapered, so the traced weights only count in the endgame.
+     */
+    pub(super) fn evaluate_endgame(&self, board: &Board, trace: &mut impl Tracer) -> Option<Score> {
+        for strong_side in [Color::White, Color::Black] {
+            let weak_side = -strong_side;
+            let score = if Self::is_bare_king(board, weak_side) {
+                if Self::has_only(board, strong_side, &[(WBishop, 1), (WKnight, 1)]) {
+                    let score = Self::kbnk(board, strong_side);
+                    trace.add_constant(strong_side, TaperedScore::new(score, score));
+                    score
+                } else if Self::count(board, WQueen, strong_side) + Self::count(board, WRook, strong_side) > 0 {
+                    Self::mop_up(board, strong_side, trace)
+                } else {
+                    continue;
+                }
+            } else if Self::has_only(board, strong_side, &[(WRook, 1)]) && Self::has_only(board, weak_side, &[(WPawn, 1)]) {
+                self.krkp(board, strong_side, trace)
+            } else {
+                continue;
+            };
+            return Some((score * strong_side as i32) as Score);
+        }
+        None
+    }
+
+    // Scales down the endgame score for drawish material signatures. strong_side is the side the eval favours
+    pub(super) fn scale_factor(board: &Board, strong_side: Color) -> i32 {
+        let weak_side = -strong_side;
+
+        // Pawnless endgames where the strong side is up a single minor piece at most
+        if Self::count(board, WPawn, strong_side) == 0 {
+            let strong_material = Self::non_pawn_material(board, strong_side);
+            if strong_material - Self::non_pawn_material(board, weak_side) <= GAME_PHASE_INCREMENT[WBishop as usize] {
+                return if strong_material <= GAME_PHASE_INCREMENT[WBishop as usize] {SCALE_FACTOR_DRAW} else {SCALE_FACTOR_PAWNLESS_MINOR_UP};
+            }
+        }
+
+        let strong_bishops = board.bbs[PieceType::from_color(WBishop, strong_side) as usize];
+
+        // Bishop and rook pawns, where the bishop doesn't control the promotion square and the enemy king holds the corner
+        if Self::has_only(board, strong_side, &[(WBishop, 1), (WPawn, 0)]) {
+            let pawns = board.bbs[PieceType::from_color(WPawn, strong_side) as usize];
+            for x in [0, 7] {
+                if pawns & !precomputed::FILES[x] == precomputed::EMPTY {
+                    let promotion_square = util::square_from_coord(x, if strong_side == Color::White {7} else {0});
+                    let weak_king_square = Self::king_square(board, weak_side);
+                    if Self::is_light_square(promotion_square) != (strong_bishops & precomputed::LIGHT_SQUARES != precomputed::EMPTY)
+                        && util::square_distance(weak_king_square, promotion_square) <= 1
+                    {
+                        return SCALE_FACTOR_DRAW;
+                    }
+                }
+            }
+        }
+
+        // Opposite coloured bishops
+        let weak_bishops = board.bbs[PieceType::from_color(WBishop, weak_side) as usize];
+        if strong_bishops.count_ones() == 1 && weak_bishops.count_ones() == 1
+            && (strong_bishops & precomputed::LIGHT_SQUARES == precomputed::EMPTY) != (weak_bishops & precomputed::LIGHT_SQUARES == precomputed::EMPTY)
+        {
+            let bishops_only = Self::non_pawn_material(board, strong_side) == GAME_PHASE_INCREMENT[WBishop as usize]
+                && Self::non_pawn_material(board, weak_side) == GAME_PHASE_INCREMENT[WBishop as usize];
+            return if bishops_only {SCALE_FACTOR_OPPOSITE_BISHOPS} else {SCALE_FACTOR_OPPOSITE_BISHOPS_WITH_PIECES};
+        }
+
+        SCALE_FACTOR_NORMAL
+    }
+
+    // Scales the endgame part of the score, and returns the scale factor that was used
+    pub(super) fn apply_scale_factor(score: &mut TaperedScore, board: &Board) -> i32 {
+        let strong_side = if score.eg > 0 {Color::White} else {Color::Black};
+        let scale_factor = Self::scale_factor(board, strong_side);
+        score.eg = score.eg * scale_factor / SCALE_FACTOR_NORMAL;
+        scale_factor
+    }
+
+    // The weak king has to be driven to a corner of the same colour as the bishop
+    fn kbnk(board: &Board, strong_side: Color) -> i32 {
+        let weak_king_square = Self::king_square(board, -strong_side);
+        let bishop = board.bbs[PieceType::from_color(WBishop, strong_side) as usize];
+        let corners = if bishop & precomputed::LIGHT_SQUARES != precomputed::EMPTY {
+            [precomputed::H1, precomputed::A8]
+        } else {
+            [precomputed::A1, precomputed::H8]
+        };
+        let corner_distance = corners.iter().map(|&corner| Self::manhattan_distance(weak_king_square, corner)).min().unwrap_or(0);
+
+        KNOWN_WIN
+            + PUSH_TO_CORNER * (14 - corner_distance)
+            + PUSH_CLOSE * (14 - Self::manhattan_distance(weak_king_square, Self::king_square(board, strong_side)))
+    }
+
+    // Drive the lone king to the edge, and bring the kings together
+    fn mop_up<T: Tracer>(board: &Board, strong_side: Color, trace: &mut T) -> i32 {
+        let weak_king_square = Self::king_square(board, -strong_side);
+        let x = util::get_square_x(weak_king_square) as i32;
+        let y = util::get_square_y(weak_king_square) as i32;
+        let center_distance = std::cmp::max(3 - x, x - 4) + std::cmp::max(3 - y, y - 4);
+
+        let score = KNOWN_WIN
+            + PUSH_TO_EDGE * center_distance
+            + PUSH_CLOSE * (14 - Self::manhattan_distance(weak_king_square, Self::king_square(board, strong_side)));
+        // The material is the endgame part of the piece-square eval
+        if T::ENABLED {
+            Self::trace_psqt(board, trace);
+        }
+        trace.add_constant(strong_side, TaperedScore::new(score, score));
+        score + (board.eg_score as i32) * strong_side as i32
+    }
+
+    // Based on Stockfish's KRKP evaluator
+    fn krkp(&self, board: &Board, strong_side: Color, trace: &mut impl Tracer) -> i32 {
+        let weak_side = -strong_side;
+        let strong_king = Self::king_square(board, strong_side);
+        let weak_king = Self::king_square(board, weak_side);
+        let rook = util::ls1b_from_bitboard(board.bbs[PieceType::from_color(WRook, strong_side) as usize]);
+        let pawn = util::ls1b_from_bitboard(board.bbs[PieceType::from_color(WPawn, weak_side) as usize]);
+
+        let (front_spans, push) = match weak_side {
+            Color::White => (precomputed::WHITE_FRONT_SPANS, 8),
+            Color::Black => (precomputed::BLACK_FRONT_SPANS, -8)
+        };
+        let queening_square = util::square_from_coord(util::get_square_x(pawn) as usize, if weak_side == Color::White {7} else {0});
+        let push_square = (pawn as i32 + push) as Square;
+        // Rows from the perspective of the strong side, the pawn moves towards row 0
+        let relative_y = |sq: Square| if strong_side == Color::White {util::get_square_y(sq)} else {7 - util::get_square_y(sq)};
+        let distance = |sq1: Square, sq2: Square| util::square_distance(sq1, sq2) as i32;
+        let rook_value = self.params.piece_values[WRook as usize].eg;
+        let weak_to_move = (board.gs.player_to_move == weak_side) as i32;
+        let strong_to_move = 1 - weak_to_move;
+
+        if front_spans[pawn as usize] & util::bitboard_from_square(strong_king) != precomputed::EMPTY
+            || (distance(weak_king, pawn) >= 3 + weak_to_move && distance(weak_king, rook) >= 3)
+        {
+            // The strong king is in front of the pawn, or the weak king is too far away to support it
+            trace.add(strong_side, |t| &mut t.piece_values[WRook as usize], 1);
+            trace.add_constant(strong_side, TaperedScore::new(-distance(strong_king, pawn), -distance(strong_king, pawn)));
+            return rook_value - distance(strong_king, pawn);
+        }
+
+        let score = if relative_y(weak_king) <= 2 && distance(weak_king, pawn) == 1
+            && relative_y(strong_king) >= 3 && distance(strong_king, pawn) > 2 + strong_to_move
+        {
+            // The pawn is far advanced and supported, while the strong king is too far away
+            40 - 4 * distance(strong_king, pawn)
+        } else {
+            100 - 4 * (distance(strong_king, push_square) - distance(weak_king, push_square) - distance(pawn, queening_square))
+        };
+        trace.add_constant(strong_side, TaperedScore::new(score, score));
+        score
+    }
+
+    fn king_square(board: &Board, color: Color) -> Square {
+        util::ls1b_from_bitboard(board.bbs[PieceType::from_color(WKing, color) as usize])
+    }
+
+    fn count(board: &Board, pt: PieceType, color: Color) -> i32 {
+        board.bbs[PieceType::from_color(pt, color) as usize].count_ones() as i32
+    }
+
+    // Knights and bishops count as 1, rooks as 2 and queens as 4
+    fn non_pawn_material(board: &Board, color: Color) -> i32 {
+        [WKnight, WBishop, WRook, WQueen].iter().map(|&pt| Self::count(board, pt, color) * GAME_PHASE_INCREMENT[pt as usize]).sum()
+    }
+
+    fn is_bare_king(board: &Board, color: Color) -> bool {
+        board.bbs[PieceType::from_color(AnyWhite, color) as usize] == board.bbs[PieceType::from_color(WKing, color) as usize]
+    }
+
+    // Whether the given color has exactly these pieces besides its king. A count of 0 means at least one of that piece
+    fn has_only(board: &Board, color: Color, pieces: &[(PieceType, i32)]) -> bool {
+        let mut remaining: Bitboard = board.bbs[PieceType::from_color(AnyWhite, color) as usize] ^ board.bbs[PieceType::from_color(WKing, color) as usize];
+        for &(pt, n) in pieces {
+            let bb = board.bbs[PieceType::from_color(pt, color) as usize];
+            if (n > 0 && bb.count_ones() as i32 != n) || (n == 0 && bb == precomputed::EMPTY) {
+                return false;
+            }
+            remaining &= !bb;
+        }
+        remaining == precomputed::EMPTY
+    }
+
+    fn manhattan_distance(sq1: Square, sq2: Square) -> i32 {
+        let dx = util::get_square_x(sq1).abs_diff(util::get_square_x(sq2));
+        let dy = util::get_square_y(sq1).abs_diff(util::get_square_y(sq2));
+        (dx + dy) as i32
+    }
+
+    fn is_light_square(sq: Square) -> bool {
+        util::bitboard_from_square(sq) & precomputed::LIGHT_SQUARES != precomputed::EMPTY
+    }
+}
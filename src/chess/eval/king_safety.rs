@@ -0,0 +1,115 @@
+use super::{
+    Eval, TaperedScore, AttackMaps, Tracer,
+    super::{Board, Square, Color, PieceType::{self, *}, precomputed, util}
+};
+
+// Attack units per attacked king zone square, indexed by the piece type of the attacker
+pub(super) const ATTACK_WEIGHTS: [i32; 6] = [0, 2, 2, 3, 5, 0];
+// Attacks into the king zone only count if at least this many pieces take part
+pub(super) const MIN_KING_ATTACKERS: i32 = 2;
+
+// Attack units per file around the king
+pub(super) const SHIELD_PAWN_ADVANCED: i32 = 1; // two rows in front of the king
+pub(super) const SHIELD_PAWN_MISSING: i32 = 2;
+pub(super) const SEMI_OPEN_FILE_NEAR_KING: i32 = 1;
+pub(super) const OPEN_FILE_NEAR_KING: i32 = 2;
+
+pub(super) const MAX_ATTACK_UNITS: usize = 99;
+
+// The middlegame penalty for a number of attack units, growing faster as the attack builds up
+const SAFETY_PENALTIES: [i32; MAX_ATTACK_UNITS + 1] = [
+      0,   0,   1,   2,   3,   5,   7,   9,  12,  15,
+     18,  22,  26,  30,  35,  39,  44,  50,  56,  62,
+     68,  75,  82,  85,  89,  97, 105, 113, 122, 131,
+    140, 150, 169, 180, 191, 202, 213, 225, 237, 248,
+    260, 272, 283, 295, 307, 319, 330, 342, 354, 366,
+    377, 389, 401, 412, 424, 436, 448, 459, 471, 483,
+    494, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500
+];
+const EG_SAFETY_DIVISOR: i32 = 4;
+
+// Converts attack units into a penalty. Every entry is a separate weight, so the tuner can fit the shape of the curve
+pub(super) const SAFETY_TABLE: [TaperedScore; MAX_ATTACK_UNITS + 1] = {
+    let mut table = [TaperedScore::ZERO; MAX_ATTACK_UNITS + 1];
+    let mut units = 0;
+    while units <= MAX_ATTACK_UNITS {
+        table[units] = TaperedScore::new(SAFETY_PENALTIES[units], SAFETY_PENALTIES[units] / EG_SAFETY_DIVISOR);
+        units += 1;
+    }
+    table
+};
+
+impl Eval {
+    // King safety from white's perspective
+    pub(super) fn evaluate_king_safety(&self, board: &Board, attacks: &AttackMaps, trace: &mut impl Tracer) -> TaperedScore {
+        self.king_danger(board, attacks, Color::Black, trace) - self.king_danger(board, attacks, Color::White, trace)
+    }
+
+    // Penalty for the king of the given color
+    fn king_danger(&self, board: &Board, attacks: &AttackMaps, color: Color, trace: &mut impl Tracer) -> TaperedScore {
+        let p = &self.params;
+        let king = board.bbs[PieceType::from_color(WKing, color) as usize];
+        if king == precomputed::EMPTY {
+            return TaperedScore::ZERO;
+        }
+        let king_square = util::ls1b_from_bitboard(king);
+
+        // The squares around the king, extended by one row towards the enemy
+        let mut king_zone = king | precomputed::KING_MOVES[king_square as usize];
+        king_zone |= match color {
+            Color::White => king_zone << 8,
+            Color::Black => king_zone >> 8
+        };
+
+        let mut attackers = 0;
+        let mut attack_units = 0;
+        for pt in [WKnight, WBishop, WRook, WQueen] {
+            let mut pieces = board.bbs[PieceType::from_color(pt, -color) as usize];
+            while pieces != precomputed::EMPTY {
+                let sq = util::pop_ls1b(&mut pieces);
+                let zone_attacks = attacks.piece(sq) & king_zone;
+                if zone_attacks != precomputed::EMPTY {
+                    attackers += 1;
+                    attack_units += p.attack_weights[pt as usize] * zone_attacks.count_ones() as i32;
+                }
+            }
+        }
+        if attackers < p.min_king_attackers {
+            attack_units = 0;
+        }
+
+        let units = std::cmp::min((attack_units + self.pawn_shield_units(board, color, king_square)) as usize, MAX_ATTACK_UNITS);
+        trace.add(color, |t| &mut t.safety_table[units], -1);
+        p.safety_table[units]
+    }
+
+    // Attack units for missing or advanced shield pawns and (semi-)open files on and next to the king file
+    fn pawn_shield_units(&self, board: &Board, color: Color, king_square: Square) -> i32 {
+        let p = &self.params;
+        let own_pawns = board.bbs[PieceType::from_color(WPawn, color) as usize];
+        let enemy_pawns = board.bbs[PieceType::from_color(WPawn, -color) as usize];
+        let king_x = util::get_square_x(king_square) as usize;
+        let king_y = util::get_square_y(king_square) as isize;
+        let forward = if color == Color::White {1} else {-1};
+
+        let mut units = 0;
+        for x in king_x.saturating_sub(1)..=std::cmp::min(king_x + 1, 7) {
+            let file = precomputed::FILES[x];
+            if file & own_pawns == precomputed::EMPTY {
+                units += if file & enemy_pawns == precomputed::EMPTY {p.open_file_near_king} else {p.semi_open_file_near_king};
+            }
+
+            let row_bb = |distance: isize| {
+                let y = king_y + distance * forward;
+                if (0..8).contains(&y) {precomputed::ROWS[y as usize]} else {precomputed::EMPTY}
+            };
+            if own_pawns & file & row_bb(1) == precomputed::EMPTY {
+                units += if own_pawns & file & row_bb(2) != precomputed::EMPTY {p.shield_pawn_advanced} else {p.shield_pawn_missing};
+            }
+        }
+        units
+    }
+}
@@ -0,0 +1,148 @@
+use std::{error::Error, fs};
+
+use super::super::{Board, Color, Score, Square, CHECKMATE_SCORE, precomputed, util};
+
+mod simd;
+
+mod trainer;
+pub use trainer::{Trainer, TrainingParams};
+
+/*
+    Efficiently updatable neural network: (768 -> HIDDEN)x2 -> 1.
+    Both sides have an accumulator with the sum of the feature weights of all pieces from their perspective.
+    The output layer takes the clipped accumulator of the side to move, followed by the one of the other side.
+ */
+pub const INPUTS: usize = 768;
+pub const HIDDEN: usize = 256;
+
+// Quantisation of the hidden layer and the output weights, and the scale from network output to centipawns
+pub const QA: i32 = 255;
+pub const QB: i32 = 64;
+pub const SCALE: i32 = 400;
+
+// Keeps network output away from mate scores
+const MAX_NETWORK_SCORE: i32 = CHECKMATE_SCORE as i32 / 2;
+
+// Little endian i16 weights, in the order of the fields below
+const NETWORK_SIZE: usize = 2 * (INPUTS * HIDDEN + HIDDEN + 2 * HIDDEN + 1);
+// Trainers usually pad the network file
+const MAX_PADDING: usize = 64;
+
+pub struct Network {
+    // Indexed by feature, then by hidden neuron
+    pub feature_weights: Box<[i16]>,
+    pub feature_bias: Box<[i16]>,
+    // The side to move first, then the other side
+    pub output_weights: Box<[i16]>,
+    pub output_bias: i16
+}
+
+impl Network {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if bytes.len() < NETWORK_SIZE || bytes.len() >= NETWORK_SIZE + MAX_PADDING {
+            return Err(format!("expected a network of {} bytes, got {}", NETWORK_SIZE, bytes.len()).into());
+        }
+
+        let mut values = bytes[..NETWORK_SIZE].chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]]));
+        let mut take = |n: usize| values.by_ref().take(n).collect::<Box<[i16]>>();
+        let feature_weights = take(INPUTS * HIDDEN);
+        let feature_bias = take(HIDDEN);
+        let output_weights = take(2 * HIDDEN);
+        let output_bias = take(1)[0];
+
+        Ok(Self { feature_weights, feature_bias, output_weights, output_bias })
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.feature_weights.iter()
+            .chain(self.feature_bias.iter())
+            .chain(self.output_weights.iter())
+            .chain(std::iter::once(&self.output_bias))
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    fn feature_weights(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * HIDDEN..(feature + 1) * HIDDEN]
+    }
+
+    // Score from the perspective of the side to move
+    pub fn evaluate(&self, accumulator: &Accumulator, player_to_move: Color) -> Score {
+        let us = Accumulator::index(player_to_move);
+        let sum = simd::crelu_dot(&accumulator.values[us], &self.feature_bias, &self.output_weights[..HIDDEN])
+            .wrapping_add(simd::crelu_dot(&accumulator.values[1 - us], &self.feature_bias, &self.output_weights[HIDDEN..]));
+        let score = (sum as i64 + self.output_bias as i64) * SCALE as i64 / (QA * QB) as i64;
+        score.clamp(-MAX_NETWORK_SCORE as i64, MAX_NETWORK_SCORE as i64) as Score
+    }
+}
+
+/*
+    The sums of the feature weights of all pieces, for both perspectives. The feature bias is added when evaluating,
+    so an empty board has an empty accumulator.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Accumulator {
+    pub values: [[i16; HIDDEN]; 2]
+}
+
+impl Accumulator {
+    pub const fn new() -> Self {
+        Self { values: [[0; HIDDEN]; 2] }
+    }
+
+    pub fn index(perspective: Color) -> usize {
+        match perspective {
+            Color::White => 0,
+            Color::Black => 1
+        }
+    }
+
+    // Own pieces come first. The board is flipped vertically for black
+    pub fn feature(perspective: Color, pt: usize, sq: Square) -> usize {
+        let (own, sq) = match perspective {
+            Color::White => (pt < 6, sq as usize),
+            Color::Black => (pt >= 6, sq as usize ^ 56)
+        };
+        (!own as usize) * 384 + (pt % 6) * 64 + sq
+    }
+
+    // pt is a piece type from WPawn to BKing
+    pub fn add(&mut self, network: &Network, pt: usize, sq: Square) {
+        for perspective in [Color::White, Color::Black] {
+            simd::add_assign(&mut self.values[Self::index(perspective)], network.feature_weights(Self::feature(perspective, pt, sq)));
+        }
+    }
+
+    pub fn remove(&mut self, network: &Network, pt: usize, sq: Square) {
+        for perspective in [Color::White, Color::Black] {
+            simd::sub_assign(&mut self.values[Self::index(perspective)], network.feature_weights(Self::feature(perspective, pt, sq)));
+        }
+    }
+
+    // Full recompute from the pieces on the board
+    pub fn refresh(network: &Network, board: &Board) -> Self {
+        let mut accumulator = Self::new();
+        for pt in 0..12 {
+            let mut pieces = board.bbs[pt];
+            while pieces != precomputed::EMPTY {
+                accumulator.add(network, pt, util::pop_ls1b(&mut pieces));
+            }
+        }
+        accumulator
+    }
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,86 @@
+use super::{HIDDEN, QA};
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+// AVX2 works on 16 i16 values at once
+const _: () = assert!(HIDDEN.is_multiple_of(16));
+
+/*
+    The accumulator updates wrap around, so removing a piece exactly undoes adding it.
+    The AVX2 versions are used when the CPU supports them, and give exactly the same results as the scalar versions.
+ */
+#[inline(always)]
+pub fn add_assign(values: &mut [i16; HIDDEN], weights: &[i16]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        return unsafe { add_assign_avx2(values, weights) };
+    }
+    values.iter_mut().zip(weights).for_each(|(value, weight)| *value = value.wrapping_add(*weight));
+}
+
+#[inline(always)]
+pub fn sub_assign(values: &mut [i16; HIDDEN], weights: &[i16]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        return unsafe { sub_assign_avx2(values, weights) };
+    }
+    values.iter_mut().zip(weights).for_each(|(value, weight)| *value = value.wrapping_sub(*weight));
+}
+
+// The dot product of the clipped (to 0..=QA) hidden layer with the output weights
+#[inline(always)]
+pub fn crelu_dot(values: &[i16; HIDDEN], bias: &[i16], weights: &[i16]) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        return unsafe { crelu_dot_avx2(values, bias, weights) };
+    }
+    values.iter().zip(bias).zip(weights).fold(0i32, |sum, ((value, bias), weight)| {
+        sum.wrapping_add(value.saturating_add(*bias).clamp(0, QA as i16) as i32 * *weight as i32)
+    })
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn add_assign_avx2(values: &mut [i16; HIDDEN], weights: &[i16]) {
+    debug_assert!(weights.len() == HIDDEN);
+    for i in (0..HIDDEN).step_by(16) {
+        let value = _mm256_loadu_si256(values.as_ptr().add(i) as *const __m256i);
+        let weight = _mm256_loadu_si256(weights.as_ptr().add(i) as *const __m256i);
+        _mm256_storeu_si256(values.as_mut_ptr().add(i) as *mut __m256i, _mm256_add_epi16(value, weight));
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn sub_assign_avx2(values: &mut [i16; HIDDEN], weights: &[i16]) {
+    debug_assert!(weights.len() == HIDDEN);
+    for i in (0..HIDDEN).step_by(16) {
+        let value = _mm256_loadu_si256(values.as_ptr().add(i) as *const __m256i);
+        let weight = _mm256_loadu_si256(weights.as_ptr().add(i) as *const __m256i);
+        _mm256_storeu_si256(values.as_mut_ptr().add(i) as *mut __m256i, _mm256_sub_epi16(value, weight));
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn crelu_dot_avx2(values: &[i16; HIDDEN], bias: &[i16], weights: &[i16]) -> i32 {
+    debug_assert!(bias.len() == HIDDEN && weights.len() == HIDDEN);
+    let zero = _mm256_setzero_si256();
+    let max = _mm256_set1_epi16(QA as i16);
+    let mut sum = _mm256_setzero_si256();
+
+    for i in (0..HIDDEN).step_by(16) {
+        let value = _mm256_loadu_si256(values.as_ptr().add(i) as *const __m256i);
+        let bias = _mm256_loadu_si256(bias.as_ptr().add(i) as *const __m256i);
+        let weight = _mm256_loadu_si256(weights.as_ptr().add(i) as *const __m256i);
+        let clipped = _mm256_min_epi16(_mm256_max_epi16(_mm256_adds_epi16(value, bias), zero), max);
+        // Multiplies and adds adjacent pairs into i32, a pair can't overflow since the clipped values are at most QA
+        sum = _mm256_add_epi32(sum, _mm256_madd_epi16(clipped, weight));
+    }
+
+    let sum = _mm_add_epi32(_mm256_castsi256_si128(sum), _mm256_extracti128_si256(sum, 1));
+    let sum = _mm_add_epi32(sum, _mm_shuffle_epi32(sum, 0b01_00_11_10));
+    let sum = _mm_add_epi32(sum, _mm_shuffle_epi32(sum, 0b10_11_00_01));
+    _mm_cvtsi128_si32(sum)
+}
@@ -0,0 +1,257 @@
+use std::{error::Error, thread, time::Instant};
+
+use rand::{Rng, SeedableRng, seq::SliceRandom};
+use rand_chacha::ChaCha8Rng;
+
+use super::{
+    Network, Accumulator, INPUTS, HIDDEN, QA, QB, SCALE,
+    super::super::{Color, PackedPosition, PackedReader}
+};
+
+// All weights are stored in a single vector, so the optimizer can treat them the same
+const FEATURE_WEIGHTS: usize = 0;
+const FEATURE_BIAS: usize = FEATURE_WEIGHTS + INPUTS * HIDDEN;
+const OUTPUT_WEIGHTS: usize = FEATURE_BIAS + HIDDEN;
+const OUTPUT_BIAS: usize = OUTPUT_WEIGHTS + 2 * HIDDEN;
+const WEIGHT_COUNT: usize = OUTPUT_BIAS + 1;
+
+// Converts centipawns to a win probability: sigmoid(score / EVAL_SCALE)
+const EVAL_SCALE: f32 = 400.0;
+// Keeps the quantised weights (and the accumulator) well within i16
+const WEIGHT_CLIP: f32 = 1.98;
+
+const BETA1: f32 = 0.9;
+const BETA2: f32 = 0.999;
+const EPSILON: f32 = 1e-8;
+
+pub struct TrainingParams {
+    pub epochs: usize,
+    pub batch_size: usize,
+    pub learning_rate: f32,
+    // Weight of the game result in the target, the rest is the search score
+    pub wdl: f32,
+    pub threads: usize,
+    // Positions are shuffled in chunks of this size
+    pub shuffle_buffer: usize,
+    pub seed: u64
+}
+
+impl TrainingParams {
+    pub fn new() -> Self {
+        Self {
+            epochs: 10,
+            batch_size: 16384,
+            learning_rate: 0.001,
+            wdl: 0.3,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            shuffle_buffer: 1 << 20,
+            seed: 0
+        }
+    }
+}
+
+impl Default for TrainingParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The active features of a position from the perspective of the side to move and of the other side
+struct Sample {
+    features: [[u16; 32]; 2],
+    count: usize,
+    target: f32
+}
+
+impl Sample {
+    fn new(position: &PackedPosition, wdl: f32) -> Self {
+        let us = position.player_to_move;
+        let mut features = [[0; 32]; 2];
+        let mut count = 0;
+        for (pt, sq) in position.pieces() {
+            features[0][count] = Accumulator::feature(us, pt, sq) as u16;
+            features[1][count] = Accumulator::feature(-us, pt, sq) as u16;
+            count += 1;
+        }
+
+        let (score, result) = match us {
+            Color::White => (position.score as f32, position.white_result()),
+            Color::Black => (-position.score as f32, 1.0 - position.white_result())
+        };
+        Self { features, count, target: wdl * result + (1.0 - wdl) * sigmoid(score / EVAL_SCALE) }
+    }
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/*
+    Trains the network architecture of the evaluator on the CPU.
+    The loss is the squared error between sigmoid(eval) and an interpolation of the score and the game result.
+    Weights are trained as floats with Adam, and quantised when the network is written.
+ */
+pub struct Trainer {
+    weights: Vec<f32>,
+    m: Vec<f32>,
+    v: Vec<f32>,
+    step: i32,
+    params: TrainingParams
+}
+
+impl Trainer {
+    pub fn new(params: TrainingParams) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(params.seed);
+        let mut weights = vec![0.0; WEIGHT_COUNT];
+        let feature_range = 1.0 / (INPUTS as f32).sqrt();
+        let output_range = 1.0 / (2.0 * HIDDEN as f32).sqrt();
+        weights[FEATURE_WEIGHTS..FEATURE_BIAS].iter_mut().for_each(|w| *w = rng.gen_range(-feature_range..feature_range));
+        weights[OUTPUT_WEIGHTS..OUTPUT_BIAS].iter_mut().for_each(|w| *w = rng.gen_range(-output_range..output_range));
+        Self::with_weights(weights, params)
+    }
+
+    // Continues training from a quantised network
+    pub fn from_network(network: &Network, params: TrainingParams) -> Self {
+        let mut weights = Vec::with_capacity(WEIGHT_COUNT);
+        weights.extend(network.feature_weights.iter().chain(network.feature_bias.iter()).map(|&w| w as f32 / QA as f32));
+        weights.extend(network.output_weights.iter().map(|&w| w as f32 / QB as f32));
+        weights.push(network.output_bias as f32 / (QA * QB) as f32);
+        Self::with_weights(weights, params)
+    }
+
+    fn with_weights(weights: Vec<f32>, params: TrainingParams) -> Self {
+        Self { weights, m: vec![0.0; WEIGHT_COUNT], v: vec![0.0; WEIGHT_COUNT], step: 0, params }
+    }
+
+    pub fn network(&self) -> Network {
+        let quantise = |range: std::ops::Range<usize>, scale: i32| self.weights[range].iter()
+            .map(|&w| (w * scale as f32).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16)
+            .collect::<Box<[i16]>>();
+        Network {
+            feature_weights: quantise(FEATURE_WEIGHTS..FEATURE_BIAS, QA),
+            feature_bias: quantise(FEATURE_BIAS..OUTPUT_WEIGHTS, QA),
+            output_weights: quantise(OUTPUT_WEIGHTS..OUTPUT_BIAS, QB),
+            output_bias: quantise(OUTPUT_BIAS..WEIGHT_COUNT, QA * QB)[0]
+        }
+    }
+
+    // Adds the gradient of the loss of a single sample, and returns the loss
+    fn backpropagate(&self, sample: &Sample, gradient: &mut [f32]) -> f32 {
+        let w = &self.weights;
+        let mut hidden = [[0.0; HIDDEN]; 2];
+        for (perspective, hidden) in hidden.iter_mut().enumerate() {
+            hidden.copy_from_slice(&w[FEATURE_BIAS..OUTPUT_WEIGHTS]);
+            for &feature in &sample.features[perspective][..sample.count] {
+                let offset = FEATURE_WEIGHTS + feature as usize * HIDDEN;
+                hidden.iter_mut().zip(&w[offset..offset + HIDDEN]).for_each(|(h, w)| *h += w);
+            }
+        }
+
+        let mut output = w[OUTPUT_BIAS];
+        for (perspective, hidden) in hidden.iter().enumerate() {
+            let offset = OUTPUT_WEIGHTS + perspective * HIDDEN;
+            output += hidden.iter().zip(&w[offset..offset + HIDDEN]).map(|(h, w)| h.clamp(0.0, 1.0) * w).sum::<f32>();
+        }
+
+        let prediction = sigmoid(output * SCALE as f32 / EVAL_SCALE);
+        let error = prediction - sample.target;
+        let output_gradient = 2.0 * error * prediction * (1.0 - prediction) * SCALE as f32 / EVAL_SCALE;
+
+        gradient[OUTPUT_BIAS] += output_gradient;
+        for (perspective, hidden) in hidden.iter().enumerate() {
+            let offset = OUTPUT_WEIGHTS + perspective * HIDDEN;
+            let mut hidden_gradient = [0.0; HIDDEN];
+            for j in 0..HIDDEN {
+                gradient[offset + j] += output_gradient * hidden[j].clamp(0.0, 1.0);
+                if hidden[j] > 0.0 && hidden[j] < 1.0 {
+                    hidden_gradient[j] = output_gradient * w[offset + j];
+                }
+            }
+
+            gradient[FEATURE_BIAS..OUTPUT_WEIGHTS].iter_mut().zip(&hidden_gradient).for_each(|(g, h)| *g += h);
+            for &feature in &sample.features[perspective][..sample.count] {
+                let offset = FEATURE_WEIGHTS + feature as usize * HIDDEN;
+                gradient[offset..offset + HIDDEN].iter_mut().zip(&hidden_gradient).for_each(|(g, h)| *g += h);
+            }
+        }
+
+        error * error
+    }
+
+    // The batch is split over the threads, which all have their own gradient
+    fn batch_gradient(&self, batch: &[Sample]) -> (Vec<f32>, f32) {
+        let chunk_size = batch.len().div_ceil(self.params.threads.max(1));
+        let results = thread::scope(|s| {
+            let handles = batch.chunks(chunk_size.max(1)).map(|chunk| s.spawn(move || {
+                let mut gradient = vec![0.0; WEIGHT_COUNT];
+                let loss = chunk.iter().map(|sample| self.backpropagate(sample, &mut gradient)).sum::<f32>();
+                (gradient, loss)
+            })).collect::<Vec<_>>();
+            handles.into_iter().map(|handle| handle.join().expect("Training thread panicked")).collect::<Vec<_>>()
+        });
+
+        let mut results = results.into_iter();
+        let (mut gradient, mut loss) = results.next().unwrap_or((vec![0.0; WEIGHT_COUNT], 0.0));
+        for (thread_gradient, thread_loss) in results {
+            gradient.iter_mut().zip(&thread_gradient).for_each(|(g, t)| *g += t);
+            loss += thread_loss;
+        }
+        let n = batch.len() as f32;
+        gradient.iter_mut().for_each(|g| *g /= n);
+        (gradient, loss)
+    }
+
+    fn adam_step(&mut self, gradient: &[f32]) {
+        self.step += 1;
+        let bias_correction1 = 1.0 - BETA1.powi(self.step);
+        let bias_correction2 = 1.0 - BETA2.powi(self.step);
+        for (i, &g) in gradient.iter().enumerate() {
+            self.m[i] = BETA1 * self.m[i] + (1.0 - BETA1) * g;
+            self.v[i] = BETA2 * self.v[i] + (1.0 - BETA2) * g * g;
+            let update = self.params.learning_rate * (self.m[i] / bias_correction1) / ((self.v[i] / bias_correction2).sqrt() + EPSILON);
+            self.weights[i] = (self.weights[i] - update).clamp(-WEIGHT_CLIP, WEIGHT_CLIP);
+        }
+    }
+
+    fn train_on(&mut self, samples: &mut Vec<Sample>, rng: &mut ChaCha8Rng) -> f32 {
+        samples.shuffle(rng);
+        let mut loss = 0.0;
+        for batch in samples.chunks(self.params.batch_size.max(1)) {
+            let (gradient, batch_loss) = self.batch_gradient(batch);
+            self.adam_step(&gradient);
+            loss += batch_loss;
+        }
+        samples.clear();
+        loss
+    }
+
+    // Trains on a file of packed positions, and writes the network to output after every epoch
+    pub fn train(&mut self, data: &str, output: &str) -> Result<(), Box<dyn Error>> {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.params.seed);
+
+        for epoch in 1..=self.params.epochs {
+            let start = Instant::now();
+            let mut samples = Vec::with_capacity(self.params.shuffle_buffer);
+            let mut loss = 0.0;
+            let mut count = 0;
+
+            for position in PackedReader::open(data)? {
+                samples.push(Sample::new(&position?, self.params.wdl));
+                count += 1;
+                if samples.len() >= self.params.shuffle_buffer {
+                    loss += self.train_on(&mut samples, &mut rng);
+                }
+            }
+            loss += self.train_on(&mut samples, &mut rng);
+
+            if count == 0 {
+                return Err("No positions found".into());
+            }
+            self.network().save(output)?;
+            let elapsed = start.elapsed().as_secs_f32();
+            println!("Epoch {}: loss {:.6} | {} positions | {:.0} pos/s", epoch, loss / count as f32, count, count as f32 / elapsed.max(1e-3));
+        }
+
+        Ok(())
+    }
+}
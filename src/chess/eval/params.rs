@@ -0,0 +1,235 @@
+use std::{error::Error, fs, sync::{Arc, OnceLock}};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    TaperedScore,
+    piece_square::{self, PIECE_VALUES, PIECE_SQUARE_TABLES},
+    pawns::*, passed_pawns::*, pieces::*, king_safety::*, threats::*
+};
+
+/*
+    All eval weights, loadable from a JSON file at runtime. The compiled-in values are the defaults.
+    Tapered weights are written as [mg, eg]. The piece-square tables are indexed by piece type (pawn to king)
+    and square, with a8 first (as in the PeSTO tables), and do not include the piece values.
+ */
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EvalParams {
+    pub piece_values: [TaperedScore; 6],
+    #[serde(with = "array_2d")]
+    pub piece_square_tables: [[TaperedScore; 64]; 6],
+
+    pub doubled_pawn: TaperedScore,
+    pub isolated_pawn: TaperedScore,
+    pub backward_pawn: TaperedScore,
+    pub pawn_island: TaperedScore,
+    pub connected_pawn: [TaperedScore; 8],
+
+    pub passed_pawn: [TaperedScore; 8],
+    pub passed_pawn_free_path: [TaperedScore; 8],
+    pub passed_pawn_safe_path: [TaperedScore; 8],
+    pub passed_pawn_enemy_king_distance: [TaperedScore; 8],
+    pub passed_pawn_own_king_distance: [TaperedScore; 8],
+    pub unstoppable_passed_pawn: TaperedScore,
+
+    pub knight_mobility: [TaperedScore; 9],
+    pub bishop_mobility: [TaperedScore; 14],
+    pub rook_mobility: [TaperedScore; 15],
+    pub queen_mobility: [TaperedScore; 28],
+    pub rook_open_file: TaperedScore,
+    pub rook_semi_open_file: TaperedScore,
+    pub rook_on_seventh: TaperedScore,
+    pub knight_outpost: TaperedScore,
+    pub bishop_pair: TaperedScore,
+
+    // King safety is measured in attack units, which are converted into a penalty by the safety table
+    pub attack_weights: [i32; 6],
+    pub min_king_attackers: i32,
+    pub shield_pawn_advanced: i32,
+    pub shield_pawn_missing: i32,
+    pub semi_open_file_near_king: i32,
+    pub open_file_near_king: i32,
+    #[serde(with = "big_array")]
+    pub safety_table: [TaperedScore; MAX_ATTACK_UNITS + 1],
+
+    pub threat_by_pawn: TaperedScore,
+    pub threat_by_minor: [TaperedScore; 6],
+    pub threat_by_rook: TaperedScore,
+    pub hanging_piece: TaperedScore,
+    pub pawn_push_threat: TaperedScore,
+
+    // The piece-square tables including piece values, for both colors. Indexed by PieceType and square
+    #[serde(skip, default = "empty_pqts")]
+    pub pqts: [[TaperedScore; 64]; 12]
+}
+
+impl EvalParams {
+    pub const DEFAULT: Self = Self {
+        piece_values: PIECE_VALUES,
+        piece_square_tables: PIECE_SQUARE_TABLES,
+
+        doubled_pawn: DOUBLED_PAWN,
+        isolated_pawn: ISOLATED_PAWN,
+        backward_pawn: BACKWARD_PAWN,
+        pawn_island: PAWN_ISLAND,
+        connected_pawn: CONNECTED_PAWN,
+
+        passed_pawn: PASSED_PAWN,
+        passed_pawn_free_path: PASSED_PAWN_FREE_PATH,
+        passed_pawn_safe_path: PASSED_PAWN_SAFE_PATH,
+        passed_pawn_enemy_king_distance: PASSED_PAWN_ENEMY_KING_DISTANCE,
+        passed_pawn_own_king_distance: PASSED_PAWN_OWN_KING_DISTANCE,
+        unstoppable_passed_pawn: UNSTOPPABLE_PASSED_PAWN,
+
+        knight_mobility: KNIGHT_MOBILITY,
+        bishop_mobility: BISHOP_MOBILITY,
+        rook_mobility: ROOK_MOBILITY,
+        queen_mobility: QUEEN_MOBILITY,
+        rook_open_file: ROOK_OPEN_FILE,
+        rook_semi_open_file: ROOK_SEMI_OPEN_FILE,
+        rook_on_seventh: ROOK_ON_SEVENTH,
+        knight_outpost: KNIGHT_OUTPOST,
+        bishop_pair: BISHOP_PAIR,
+
+        attack_weights: ATTACK_WEIGHTS,
+        min_king_attackers: MIN_KING_ATTACKERS,
+        shield_pawn_advanced: SHIELD_PAWN_ADVANCED,
+        shield_pawn_missing: SHIELD_PAWN_MISSING,
+        semi_open_file_near_king: SEMI_OPEN_FILE_NEAR_KING,
+        open_file_near_king: OPEN_FILE_NEAR_KING,
+        safety_table: SAFETY_TABLE,
+
+        threat_by_pawn: THREAT_BY_PAWN,
+        threat_by_minor: THREAT_BY_MINOR,
+        threat_by_rook: THREAT_BY_ROOK,
+        hanging_piece: HANGING_PIECE,
+        pawn_push_threat: PAWN_PUSH_THREAT,
+
+        pqts: piece_square::generate_pqts(&PIECE_VALUES, &PIECE_SQUARE_TABLES)
+    };
+
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn Error>> {
+        let mut params: Self = serde_json::from_str(json)?;
+        params.update_pqts();
+        Ok(params)
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(compact_number_arrays(&serde_json::to_string_pretty(self)?))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    // All tapered weights in a fixed order, used for tuning. The integer king safety weights only pick the entry of the safety table
+    pub fn tapered_weights_mut(&mut self) -> Vec<&mut TaperedScore> {
+        let mut weights = Vec::new();
+        weights.extend(self.piece_values.iter_mut());
+        weights.extend(self.piece_square_tables.iter_mut().flatten());
+
+        weights.extend([&mut self.doubled_pawn, &mut self.isolated_pawn, &mut self.backward_pawn, &mut self.pawn_island]);
+        weights.extend(self.connected_pawn.iter_mut());
+
+        weights.extend(self.passed_pawn.iter_mut());
+        weights.extend(self.passed_pawn_free_path.iter_mut());
+        weights.extend(self.passed_pawn_safe_path.iter_mut());
+        weights.extend(self.passed_pawn_enemy_king_distance.iter_mut());
+        weights.extend(self.passed_pawn_own_king_distance.iter_mut());
+        weights.push(&mut self.unstoppable_passed_pawn);
+
+        weights.extend(self.knight_mobility.iter_mut());
+        weights.extend(self.bishop_mobility.iter_mut());
+        weights.extend(self.rook_mobility.iter_mut());
+        weights.extend(self.queen_mobility.iter_mut());
+        weights.extend([&mut self.rook_open_file, &mut self.rook_semi_open_file, &mut self.rook_on_seventh, &mut self.knight_outpost, &mut self.bishop_pair]);
+
+        weights.extend(self.safety_table.iter_mut());
+
+        weights.push(&mut self.threat_by_pawn);
+        weights.extend(self.threat_by_minor.iter_mut());
+        weights.extend([&mut self.threat_by_rook, &mut self.hanging_piece, &mut self.pawn_push_threat]);
+
+        weights
+    }
+
+    // Has to be called after changing the piece values or piece-square tables
+    pub fn update_pqts(&mut self) {
+        self.pqts = piece_square::generate_pqts(&self.piece_values, &self.piece_square_tables);
+    }
+}
+
+fn empty_pqts() -> [[TaperedScore; 64]; 12] {
+    [[TaperedScore::ZERO; 64]; 12]
+}
+
+// Puts arrays of numbers on a single line, since pretty printing puts every number on its own line
+fn compact_number_arrays(json: &str) -> String {
+    let lines = json.lines().collect::<Vec<&str>>();
+    let mut out = Vec::with_capacity(lines.len());
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.ends_with('[') {
+            let numbers = lines[i + 1..].iter()
+                .take_while(|l| l.trim_end_matches(',').trim().parse::<f64>().is_ok())
+                .map(|l| l.trim().trim_end_matches(','))
+                .collect::<Vec<&str>>();
+            let end = i + 1 + numbers.len();
+            if !numbers.is_empty() && end < lines.len() && lines[end].trim().starts_with(']') {
+                out.push(format!("{}{}{}", line, numbers.join(", "), lines[end].trim()));
+                i = end + 1;
+                continue;
+            }
+        }
+        out.push(line.to_string());
+        i += 1;
+    }
+
+    out.join("\n")
+}
+
+// The compiled-in weights, shared by every Eval and Board that doesn't get its own
+pub fn default_params() -> Arc<EvalParams> {
+    static DEFAULT_PARAMS: OnceLock<Arc<EvalParams>> = OnceLock::new();
+    DEFAULT_PARAMS.get_or_init(|| Arc::new(EvalParams::DEFAULT)).clone()
+}
+
+// serde only supports arrays of up to 32 elements
+mod big_array {
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer, T: Serialize, const N: usize>(array: &[T; N], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(array)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>, const N: usize>(deserializer: D) -> Result<[T; N], D::Error> {
+        let values = Vec::<T>::deserialize(deserializer)?;
+        let len = values.len();
+        values.try_into().map_err(|_| D::Error::invalid_length(len, &format!("an array of {} elements", N).as_str()))
+    }
+}
+
+mod array_2d {
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer, T: Serialize, const N: usize, const M: usize>(array: &[[T; M]; N], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(array.iter().map(|row| row.as_slice()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>, const N: usize, const M: usize>(deserializer: D) -> Result<[[T; M]; N], D::Error> {
+        let rows = Vec::<Vec<T>>::deserialize(deserializer)?;
+        let len = rows.len();
+        let rows = rows.into_iter().map(|row| {
+            let row_len = row.len();
+            row.try_into().map_err(|_| D::Error::invalid_length(row_len, &format!("a row of {} elements", M).as_str()))
+        }).collect::<Result<Vec<[T; M]>, D::Error>>()?;
+        rows.try_into().map_err(|_| D::Error::invalid_length(len, &format!("an array of {} rows", N).as_str()))
+    }
+}
@@ -0,0 +1,157 @@
+use super::{
+    Eval, TaperedScore, AttackMaps, Tracer,
+    super::{Board, Bitboard, Color, PieceType::{self, *}, precomputed, util}
+};
+
+// All passed pawn terms are indexed by the relative row of the pawn
+pub(super) const PASSED_PAWN: [TaperedScore; 8] = [
+    TaperedScore::new( 0,   0),
+    TaperedScore::new( 2,   8),
+    TaperedScore::new( 5,  12),
+    TaperedScore::new( 8,  20),
+    TaperedScore::new(20,  40),
+    TaperedScore::new(40,  75),
+    TaperedScore::new(70, 120),
+    TaperedScore::new( 0,   0),
+];
+// No pieces on the path to promotion
+pub(super) const PASSED_PAWN_FREE_PATH: [TaperedScore; 8] = [
+    TaperedScore::new( 0,  0),
+    TaperedScore::new( 0,  2),
+    TaperedScore::new( 0,  4),
+    TaperedScore::new( 2,  8),
+    TaperedScore::new( 5, 15),
+    TaperedScore::new(10, 25),
+    TaperedScore::new(15, 40),
+    TaperedScore::new( 0,  0),
+];
+// No enemy attacks on the path to promotion
+pub(super) const PASSED_PAWN_SAFE_PATH: [TaperedScore; 8] = [
+    TaperedScore::new( 0,  0),
+    TaperedScore::new( 0,  2),
+    TaperedScore::new( 0,  4),
+    TaperedScore::new( 3, 10),
+    TaperedScore::new( 6, 18),
+    TaperedScore::new(12, 30),
+    TaperedScore::new(20, 50),
+    TaperedScore::new( 0,  0),
+];
+// Per square distance of the enemy and own king to the promotion square
+pub(super) const PASSED_PAWN_ENEMY_KING_DISTANCE: [TaperedScore; 8] = [
+    TaperedScore::new(0,  0),
+    TaperedScore::new(0,  0),
+    TaperedScore::new(0,  0),
+    TaperedScore::new(0,  4),
+    TaperedScore::new(0,  8),
+    TaperedScore::new(0, 16),
+    TaperedScore::new(0, 24),
+    TaperedScore::new(0,  0),
+];
+pub(super) const PASSED_PAWN_OWN_KING_DISTANCE: [TaperedScore; 8] = [
+    TaperedScore::new(0,   0),
+    TaperedScore::new(0,   0),
+    TaperedScore::new(0,   0),
+    TaperedScore::new(0,  -2),
+    TaperedScore::new(0,  -4),
+    TaperedScore::new(0,  -8),
+    TaperedScore::new(0, -12),
+    TaperedScore::new(0,   0),
+];
+const MAX_KING_DISTANCE: u8 = 5;
+
+pub(super) const UNSTOPPABLE_PASSED_PAWN: TaperedScore = TaperedScore::new(0, 600);
+
+impl Eval {
+    // Passed pawn eval from white's perspective. passed_pawns contains the passers of both colors
+    pub(super) fn evaluate_passed_pawns(&self, board: &Board, attacks: &AttackMaps, passed_pawns: Bitboard, trace: &mut impl Tracer) -> TaperedScore {
+        let p = &self.params;
+        if passed_pawns == precomputed::EMPTY {
+            return TaperedScore::ZERO;
+        }
+
+        let (white_score, white_unstoppable) = self.passed_pawns(board, attacks, passed_pawns, Color::White, trace);
+        let (black_score, black_unstoppable) = self.passed_pawns(board, attacks, passed_pawns, Color::Black, trace);
+        let mut score = white_score - black_score;
+
+        // In a pawn race, the side that promotes first wins. The side to move is one tempo ahead
+        let white_to_move = board.gs.player_to_move == Color::White;
+        if white_unstoppable < black_unstoppable || (white_unstoppable == black_unstoppable && white_unstoppable != u8::MAX && white_to_move) {
+            score += p.unstoppable_passed_pawn;
+            trace.add(Color::White, |t| &mut t.unstoppable_passed_pawn, 1);
+        } else if black_unstoppable != u8::MAX {
+            score -= p.unstoppable_passed_pawn;
+            trace.add(Color::Black, |t| &mut t.unstoppable_passed_pawn, 1);
+        }
+
+        score
+    }
+
+    // Returns the passed pawn score and the number of moves the fastest unstoppable pawn needs to promote (u8::MAX if none)
+    fn passed_pawns(&self, board: &Board, attacks: &AttackMaps, passed_pawns: Bitboard, color: Color, trace: &mut impl Tracer) -> (TaperedScore, u8) {
+        let p = &self.params;
+        let own_passed_pawns = passed_pawns & board.bbs[PieceType::from_color(WPawn, color) as usize];
+        if own_passed_pawns == precomputed::EMPTY {
+            return (TaperedScore::ZERO, u8::MAX);
+        }
+
+        let front_spans = match color {
+            Color::White => precomputed::WHITE_FRONT_SPANS,
+            Color::Black => precomputed::BLACK_FRONT_SPANS
+        };
+        let own_king_square = util::ls1b_from_bitboard(board.bbs[PieceType::from_color(WKing, color) as usize]);
+        let enemy_king_square = util::ls1b_from_bitboard(board.bbs[PieceType::from_color(WKing, -color) as usize]);
+        let enemy_attacks = attacks.by_color(-color);
+
+        // The square rule only holds if the enemy king is the only piece that can stop the pawn
+        let enemy_pieces = board.bbs[PieceType::from_color(WKnight, -color) as usize]
+            | board.bbs[PieceType::from_color(WBishop, -color) as usize]
+            | board.bbs[PieceType::from_color(WRook, -color) as usize]
+            | board.bbs[PieceType::from_color(WQueen, -color) as usize];
+        let pawn_endgame = enemy_pieces == precomputed::EMPTY;
+        let enemy_to_move = board.gs.player_to_move != color;
+
+        let mut score = TaperedScore::ZERO;
+        let mut fastest_unstoppable = u8::MAX;
+
+        let mut pawns = own_passed_pawns;
+        while pawns != precomputed::EMPTY {
+            let sq = util::pop_ls1b(&mut pawns);
+            let x = util::get_square_x(sq) as usize;
+            let y = util::get_square_y(sq) as usize;
+            let relative_y = if color == Color::White {y} else {7 - y};
+            let promotion_square = util::square_from_coord(x, if color == Color::White {7} else {0});
+            let path = front_spans[sq as usize];
+
+            score += p.passed_pawn[relative_y];
+            trace.add(color, |t| &mut t.passed_pawn[relative_y], 1);
+
+            let free_path = path & board.bbs[AnyPiece as usize] == precomputed::EMPTY;
+            if free_path {
+                score += p.passed_pawn_free_path[relative_y];
+                trace.add(color, |t| &mut t.passed_pawn_free_path[relative_y], 1);
+            }
+            if path & enemy_attacks == precomputed::EMPTY {
+                score += p.passed_pawn_safe_path[relative_y];
+                trace.add(color, |t| &mut t.passed_pawn_safe_path[relative_y], 1);
+            }
+
+            let own_king_distance = std::cmp::min(util::square_distance(own_king_square, promotion_square), MAX_KING_DISTANCE) as i32;
+            let enemy_king_distance = std::cmp::min(util::square_distance(enemy_king_square, promotion_square), MAX_KING_DISTANCE) as i32;
+            score += p.passed_pawn_enemy_king_distance[relative_y] * enemy_king_distance;
+            score += p.passed_pawn_own_king_distance[relative_y] * own_king_distance;
+            trace.add(color, |t| &mut t.passed_pawn_enemy_king_distance[relative_y], enemy_king_distance);
+            trace.add(color, |t| &mut t.passed_pawn_own_king_distance[relative_y], own_king_distance);
+
+            if pawn_endgame && free_path {
+                // A pawn on its starting row can move two squares at once
+                let pawn_moves = if relative_y == 1 {5} else {7 - relative_y as u8};
+                let enemy_king_moves = util::square_distance(enemy_king_square, promotion_square) - enemy_to_move as u8;
+                if enemy_king_moves > pawn_moves {
+                    fastest_unstoppable = std::cmp::min(fastest_unstoppable, pawn_moves);
+                }
+            }
+        }
+
+        (score, fastest_unstoppable)
+    }
+}
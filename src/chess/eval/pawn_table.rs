@@ -0,0 +1,50 @@
+use super::{TaperedScore, super::Bitboard};
+
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+const PAWN_TABLE_MASK: u64 = (PAWN_TABLE_SIZE - 1) as u64;
+
+#[derive(Clone, Copy)]
+pub struct PawnEntry {
+    pub key: u64,
+    pub score: TaperedScore,
+    pub passed_pawns: Bitboard // of both colors
+}
+
+impl PawnEntry {
+    fn empty() -> Self {
+        Self { key: 0, score: TaperedScore::ZERO, passed_pawns: 0 }
+    }
+}
+
+// Caches the pawn structure eval, indexed by Board::pawn_key. Always-replace scheme.
+// Empty entries match pawnless positions (key 0), for which the empty entry is correct.
+pub struct PawnHashTable {
+    table: Vec<PawnEntry>
+}
+
+impl PawnHashTable {
+    pub fn new() -> Self {
+        Self {
+            table: vec![PawnEntry::empty(); PAWN_TABLE_SIZE]
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.table.fill(PawnEntry::empty());
+    }
+
+    #[inline(always)]
+    pub fn probe(&self, key: u64) -> Option<PawnEntry> {
+        let entry = self.table[(key & PAWN_TABLE_MASK) as usize];
+        if entry.key == key {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+
+    #[inline(always)]
+    pub fn store(&mut self, entry: PawnEntry) {
+        self.table[(entry.key & PAWN_TABLE_MASK) as usize] = entry;
+    }
+}
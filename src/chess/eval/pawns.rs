@@ -0,0 +1,111 @@
+use super::{
+    Eval, TaperedScore, PawnEntry, Tracer,
+    super::{Board, Bitboard, Square, Color, PieceType::{self, *}, precomputed, util}
+};
+
+pub(super) const DOUBLED_PAWN: TaperedScore = TaperedScore::new(-10, -25);
+pub(super) const ISOLATED_PAWN: TaperedScore = TaperedScore::new(-8, -12);
+pub(super) const BACKWARD_PAWN: TaperedScore = TaperedScore::new(-6, -10);
+pub(super) const PAWN_ISLAND: TaperedScore = TaperedScore::new(-5, -8);
+// Indexed by the relative row of a pawn that is supported or has a neighbour on the same row
+pub(super) const CONNECTED_PAWN: [TaperedScore; 8] = [
+    TaperedScore::new( 0,  0),
+    TaperedScore::new( 2,  1),
+    TaperedScore::new( 4,  3),
+    TaperedScore::new( 6,  5),
+    TaperedScore::new(10,  9),
+    TaperedScore::new(18, 15),
+    TaperedScore::new(30, 25),
+    TaperedScore::new( 0,  0),
+];
+
+impl Eval {
+    // Pawn structure eval from white's perspective, cached in the pawn hash table
+    pub(super) fn evaluate_pawns<T: Tracer>(&mut self, board: &Board, trace: &mut T) -> PawnEntry {
+        // A cached entry would skip the tracing
+        if !T::ENABLED {
+            if let Some(entry) = self.pawn_table.probe(board.pawn_key) {
+                return entry;
+            }
+        }
+
+        let (white_score, white_passed_pawns) = self.pawn_structure(board, Color::White, trace);
+        let (black_score, black_passed_pawns) = self.pawn_structure(board, Color::Black, trace);
+        let entry = PawnEntry {
+            key: board.pawn_key,
+            score: white_score - black_score,
+            passed_pawns: white_passed_pawns | black_passed_pawns
+        };
+        self.pawn_table.store(entry);
+        entry
+    }
+
+    // Returns the pawn structure score and the passed pawns of the given color
+    fn pawn_structure(&self, board: &Board, color: Color, trace: &mut impl Tracer) -> (TaperedScore, Bitboard) {
+        let p = &self.params;
+        let own_pawns = board.bbs[PieceType::from_color(WPawn, color) as usize];
+        let enemy_pawns = board.bbs[PieceType::from_color(WPawn, -color) as usize];
+        let enemy_pawn_attacks = util::pawn_attacks(enemy_pawns, -color);
+
+        let (forward_rows, front_spans, passed_pawn_masks, own_pawn_captures) = match color {
+            Color::White => (precomputed::WHITE_FORWARD_ROWS, precomputed::WHITE_FRONT_SPANS, precomputed::WHITE_PASSED_PAWN_MASKS, precomputed::BLACK_PAWN_CAPTURES),
+            Color::Black => (precomputed::BLACK_FORWARD_ROWS, precomputed::BLACK_FRONT_SPANS, precomputed::BLACK_PASSED_PAWN_MASKS, precomputed::WHITE_PAWN_CAPTURES)
+        };
+
+        let mut score = TaperedScore::ZERO;
+        let mut passed_pawns = precomputed::EMPTY;
+
+        let mut pawns = own_pawns;
+        while pawns != precomputed::EMPTY {
+            let sq = util::pop_ls1b(&mut pawns);
+            let x = util::get_square_x(sq) as usize;
+            let y = util::get_square_y(sq) as usize;
+            let relative_y = if color == Color::White {y} else {7 - y};
+
+            if own_pawns & front_spans[sq as usize] != precomputed::EMPTY {
+                score += p.doubled_pawn;
+                trace.add(color, |t| &mut t.doubled_pawn, 1);
+            } else if enemy_pawns & passed_pawn_masks[sq as usize] == precomputed::EMPTY {
+                passed_pawns |= util::bitboard_from_square(sq); // only the frontmost of doubled pawns can be passed
+            }
+
+            let neighbours = own_pawns & precomputed::ADJACENT_FILES[x];
+            if neighbours == precomputed::EMPTY {
+                score += p.isolated_pawn;
+                trace.add(color, |t| &mut t.isolated_pawn, 1);
+            } else if neighbours & !forward_rows[y] == precomputed::EMPTY // can never be supported by a neighbour
+                && Self::stop_square(sq, color) & enemy_pawn_attacks != precomputed::EMPTY
+            {
+                score += p.backward_pawn;
+                trace.add(color, |t| &mut t.backward_pawn, 1);
+            }
+
+            // own_pawn_captures contains the squares from which own pawns defend sq
+            let supporting = own_pawns & own_pawn_captures[sq as usize];
+            let phalanx = neighbours & precomputed::ROWS[y];
+            if supporting | phalanx != precomputed::EMPTY {
+                score += p.connected_pawn[relative_y];
+                trace.add(color, |t| &mut t.connected_pawn[relative_y], 1);
+            }
+        }
+
+        let pawn_islands = Self::pawn_islands(own_pawns);
+        trace.add(color, |t| &mut t.pawn_island, pawn_islands);
+        (score + p.pawn_island * pawn_islands, passed_pawns)
+    }
+
+    #[inline(always)]
+    fn stop_square(sq: Square, color: Color) -> Bitboard {
+        match color {
+            Color::White => util::bitboard_from_square(sq + 8),
+            Color::Black => util::bitboard_from_square(sq - 8)
+        }
+    }
+
+    fn pawn_islands(pawns: Bitboard) -> i32 {
+        let occupied_files = (0..8).fold(0u8, |acc, x| {
+            if pawns & precomputed::FILES[x] != precomputed::EMPTY {acc | 1 << x} else {acc}
+        });
+        (occupied_files & !(occupied_files << 1)).count_ones() as i32 // count the first file of every island
+    }
+}
@@ -1,6 +1,6 @@
-use super::super::Score;
-pub const MG_PIECE_SQUARE_TABLES: [[Score; 64]; 12] = generate_pqts(UNSIGNED_MG_PQTS, MG_PIECE_VALUES);
-pub const EG_PIECE_SQUARE_TABLES: [[Score; 64]; 12] = generate_pqts(UNSIGNED_EG_PQTS, EG_PIECE_VALUES);
+use super::{TaperedScore, super::Score};
+pub const PIECE_VALUES: [TaperedScore; 6] = merge_piece_values(MG_PIECE_VALUES, EG_PIECE_VALUES);
+pub const PIECE_SQUARE_TABLES: [[TaperedScore; 64]; 6] = merge_pqts(UNSIGNED_MG_PQTS, UNSIGNED_EG_PQTS);
 pub const GAME_PHASE_INCREMENT: [i32; 12] = [0, 1, 1, 2, 4, 0, 0, 1, 1, 2, 4, 0];
 pub const MAX_GAME_PHASE: i32 = GAME_PHASE_INCREMENT[0] * 16 // pawns
                              + GAME_PHASE_INCREMENT[1] * 4  // knights
@@ -127,16 +127,46 @@ const UNSIGNED_EG_PQTS: [[Score; 64]; 6] =[
     ]
 ];
 
-const fn generate_pqts(unsigned_pqts: [[Score; 64]; 6], piece_values: [Score; 6]) -> [[Score; 64]; 12] {
-    let mut pqts = [[0; 64]; 12];
+const fn merge_piece_values(mg_piece_values: [Score; 6], eg_piece_values: [Score; 6]) -> [TaperedScore; 6] {
+    let mut piece_values = [TaperedScore::ZERO; 6];
+
+    let mut pt = 0;
+    while pt < 6 {
+        piece_values[pt] = TaperedScore::new(mg_piece_values[pt] as i32, eg_piece_values[pt] as i32);
+        pt += 1;
+    }
+
+    piece_values
+}
+
+const fn merge_pqts(unsigned_mg_pqts: [[Score; 64]; 6], unsigned_eg_pqts: [[Score; 64]; 6]) -> [[TaperedScore; 64]; 6] {
+    let mut pqts = [[TaperedScore::ZERO; 64]; 6];
+
+    let mut pt = 0;
+    while pt < 6 {
+        let mut sq = 0;
+        while sq < 64 {
+            pqts[pt][sq] = TaperedScore::new(unsigned_mg_pqts[pt][sq] as i32, unsigned_eg_pqts[pt][sq] as i32);
+            sq += 1;
+        }
+        pt += 1;
+    }
+
+    pqts
+}
+
+// Adds the piece values to the tables, and mirrors them for black. Indexed by PieceType and square
+pub const fn generate_pqts(piece_values: &[TaperedScore; 6], unsigned_pqts: &[[TaperedScore; 64]; 6]) -> [[TaperedScore; 64]; 12] {
+    let mut pqts = [[TaperedScore::ZERO; 64]; 12];
 
     let mut pt = 0;
     while pt < 6 {
         let mut sq = 0;
         while sq < 64 {
             // The arrays are flipped vertically, so flip sq when indexing
-            pqts[pt][sq] = unsigned_pqts[pt][sq ^ 56] + piece_values[pt];
-            pqts[pt + 6][sq ^ 56] = -pqts[pt][sq];
+            let unsigned = unsigned_pqts[pt][sq ^ 56];
+            pqts[pt][sq] = TaperedScore::new(unsigned.mg + piece_values[pt].mg, unsigned.eg + piece_values[pt].eg);
+            pqts[pt + 6][sq ^ 56] = TaperedScore::new(-pqts[pt][sq].mg, -pqts[pt][sq].eg);
 
             sq += 1;
         }
@@ -144,4 +174,4 @@ const fn generate_pqts(unsigned_pqts: [[Score; 64]; 6], piece_values: [Score; 6]
     }
 
     pqts
-}
\ No newline at end of file
+}
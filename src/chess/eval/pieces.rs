@@ -0,0 +1,179 @@
+use super::{
+    Eval, TaperedScore, AttackMaps, Tracer,
+    super::{Board, Color, PieceType::{self, *}, precomputed, util}
+};
+
+// Indexed by the number of squares in the mobility area a piece attacks
+pub(super) const KNIGHT_MOBILITY: [TaperedScore; 9] = [
+    TaperedScore::new(-31, -35),
+    TaperedScore::new(-15, -18),
+    TaperedScore::new( -9, -10),
+    TaperedScore::new( -4,  -5),
+    TaperedScore::new(  0,   0),
+    TaperedScore::new(  4,   4),
+    TaperedScore::new(  7,   8),
+    TaperedScore::new( 10,  11),
+    TaperedScore::new( 13,  15),
+];
+pub(super) const BISHOP_MOBILITY: [TaperedScore; 14] = [
+    TaperedScore::new(-32, -38),
+    TaperedScore::new(-19, -22),
+    TaperedScore::new(-14, -16),
+    TaperedScore::new( -9, -11),
+    TaperedScore::new( -6,  -7),
+    TaperedScore::new( -3,  -3),
+    TaperedScore::new(  0,   0),
+    TaperedScore::new(  3,   3),
+    TaperedScore::new(  5,   6),
+    TaperedScore::new(  7,   8),
+    TaperedScore::new(  9,  11),
+    TaperedScore::new( 11,  13),
+    TaperedScore::new( 13,  16),
+    TaperedScore::new( 15,  18),
+];
+pub(super) const ROOK_MOBILITY: [TaperedScore; 15] = [
+    TaperedScore::new(-22, -43),
+    TaperedScore::new(-13, -26),
+    TaperedScore::new( -9, -18),
+    TaperedScore::new( -6, -13),
+    TaperedScore::new( -4,  -8),
+    TaperedScore::new( -2,  -4),
+    TaperedScore::new(  0,   0),
+    TaperedScore::new(  2,   3),
+    TaperedScore::new(  3,   7),
+    TaperedScore::new(  5,  10),
+    TaperedScore::new(  6,  13),
+    TaperedScore::new(  8,  15),
+    TaperedScore::new(  9,  18),
+    TaperedScore::new( 10,  20),
+    TaperedScore::new( 11,  23),
+];
+pub(super) const QUEEN_MOBILITY: [TaperedScore; 28] = [
+    TaperedScore::new(-23, -46),
+    TaperedScore::new(-16, -33),
+    TaperedScore::new(-14, -27),
+    TaperedScore::new(-11, -23),
+    TaperedScore::new(-10, -19),
+    TaperedScore::new( -8, -16),
+    TaperedScore::new( -7, -13),
+    TaperedScore::new( -5, -11),
+    TaperedScore::new( -4,  -8),
+    TaperedScore::new( -3,  -6),
+    TaperedScore::new( -2,  -4),
+    TaperedScore::new( -1,  -2),
+    TaperedScore::new(  0,   0),
+    TaperedScore::new(  1,   2),
+    TaperedScore::new(  2,   4),
+    TaperedScore::new(  3,   5),
+    TaperedScore::new(  4,   7),
+    TaperedScore::new(  4,   9),
+    TaperedScore::new(  5,  10),
+    TaperedScore::new(  6,  12),
+    TaperedScore::new(  7,  13),
+    TaperedScore::new(  7,  15),
+    TaperedScore::new(  8,  16),
+    TaperedScore::new(  9,  18),
+    TaperedScore::new(  9,  19),
+    TaperedScore::new( 10,  20),
+    TaperedScore::new( 11,  22),
+    TaperedScore::new( 11,  23),
+];
+
+pub(super) const ROOK_OPEN_FILE: TaperedScore = TaperedScore::new(25, 10);
+pub(super) const ROOK_SEMI_OPEN_FILE: TaperedScore = TaperedScore::new(12, 8);
+pub(super) const ROOK_ON_SEVENTH: TaperedScore = TaperedScore::new(10, 25);
+pub(super) const KNIGHT_OUTPOST: TaperedScore = TaperedScore::new(20, 10);
+pub(super) const BISHOP_PAIR: TaperedScore = TaperedScore::new(25, 50);
+
+impl Eval {
+    // Mobility and piece activity from white's perspective
+    pub(super) fn evaluate_pieces(&self, board: &Board, attacks: &AttackMaps, trace: &mut impl Tracer) -> TaperedScore {
+        self.pieces(board, attacks, Color::White, trace) - self.pieces(board, attacks, Color::Black, trace)
+    }
+
+    fn pieces(&self, board: &Board, attacks: &AttackMaps, color: Color, trace: &mut impl Tracer) -> TaperedScore {
+        let p = &self.params;
+        let own_pieces = board.bbs[PieceType::from_color(AnyWhite, color) as usize];
+        let own_pawns = board.bbs[PieceType::from_color(WPawn, color) as usize];
+        let enemy_pawns = board.bbs[PieceType::from_color(WPawn, -color) as usize];
+        // Squares attacked by enemy pawns are not counted, since a piece can't safely go there
+        let mobility_area = !(own_pieces | attacks.by_piece_type(PieceType::from_color(WPawn, -color)));
+
+        let (passed_pawn_masks, seventh_row, eighth_row) = match color {
+            Color::White => (precomputed::WHITE_PASSED_PAWN_MASKS, precomputed::ROWS[6], precomputed::ROWS[7]),
+            Color::Black => (precomputed::BLACK_PASSED_PAWN_MASKS, precomputed::ROWS[1], precomputed::ROWS[0])
+        };
+
+        let mut score = TaperedScore::ZERO;
+
+        let mut knights = board.bbs[PieceType::from_color(WKnight, color) as usize];
+        while knights != precomputed::EMPTY {
+            let sq = util::pop_ls1b(&mut knights);
+            let mobility = (attacks.piece(sq) & mobility_area).count_ones() as usize;
+            score += p.knight_mobility[mobility];
+            trace.add(color, |t| &mut t.knight_mobility[mobility], 1);
+
+            // A knight on the 4th to 6th row, supported by a pawn, that can never be attacked by enemy pawns
+            let y = util::get_square_y(sq);
+            let relative_y = if color == Color::White {y} else {7 - y};
+            let x = util::get_square_x(sq) as usize;
+            if (3..=5).contains(&relative_y)
+                && attacks.by_piece_type(PieceType::from_color(WPawn, color)) & util::bitboard_from_square(sq) != precomputed::EMPTY
+                && enemy_pawns & passed_pawn_masks[sq as usize] & precomputed::ADJACENT_FILES[x] == precomputed::EMPTY
+            {
+                score += p.knight_outpost;
+                trace.add(color, |t| &mut t.knight_outpost, 1);
+            }
+        }
+
+        let bishops = board.bbs[PieceType::from_color(WBishop, color) as usize];
+        if bishops.count_ones() >= 2 {
+            score += p.bishop_pair;
+            trace.add(color, |t| &mut t.bishop_pair, 1);
+        }
+        let mut bishops = bishops;
+        while bishops != precomputed::EMPTY {
+            let sq = util::pop_ls1b(&mut bishops);
+            let mobility = (attacks.piece(sq) & mobility_area).count_ones() as usize;
+            score += p.bishop_mobility[mobility];
+            trace.add(color, |t| &mut t.bishop_mobility[mobility], 1);
+        }
+
+        let mut rooks = board.bbs[PieceType::from_color(WRook, color) as usize];
+        while rooks != precomputed::EMPTY {
+            let sq = util::pop_ls1b(&mut rooks);
+            let mobility = (attacks.piece(sq) & mobility_area).count_ones() as usize;
+            score += p.rook_mobility[mobility];
+            trace.add(color, |t| &mut t.rook_mobility[mobility], 1);
+
+            let file = precomputed::FILES[util::get_square_x(sq) as usize];
+            if file & own_pawns == precomputed::EMPTY {
+                if file & enemy_pawns == precomputed::EMPTY {
+                    score += p.rook_open_file;
+                    trace.add(color, |t| &mut t.rook_open_file, 1);
+                } else {
+                    score += p.rook_semi_open_file;
+                    trace.add(color, |t| &mut t.rook_semi_open_file, 1);
+                }
+            }
+
+            // Only relevant if it attacks pawns or cuts off the enemy king
+            if util::bitboard_from_square(sq) & seventh_row != precomputed::EMPTY
+                && (enemy_pawns & seventh_row != precomputed::EMPTY || board.bbs[PieceType::from_color(WKing, -color) as usize] & eighth_row != precomputed::EMPTY)
+            {
+                score += p.rook_on_seventh;
+                trace.add(color, |t| &mut t.rook_on_seventh, 1);
+            }
+        }
+
+        let mut queens = board.bbs[PieceType::from_color(WQueen, color) as usize];
+        while queens != precomputed::EMPTY {
+            let sq = util::pop_ls1b(&mut queens);
+            let mobility = (attacks.piece(sq) & mobility_area).count_ones() as usize;
+            score += p.queen_mobility[mobility];
+            trace.add(color, |t| &mut t.queen_mobility[mobility], 1);
+        }
+
+        score
+    }
+}
@@ -0,0 +1,85 @@
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::super::Score;
+use super::piece_square::MAX_GAME_PHASE;
+
+// A middlegame and an endgame score, interpolated by the game phase
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TaperedScore {
+    pub mg: i32,
+    pub eg: i32
+}
+
+impl TaperedScore {
+    pub const ZERO: Self = Self::new(0, 0);
+
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        Self { mg, eg }
+    }
+
+    // mg_game_phase ranges from 0 (endgame) to MAX_GAME_PHASE (opening)
+    pub fn taper(&self, mg_game_phase: i32) -> Score {
+        let eg_game_phase = MAX_GAME_PHASE - mg_game_phase;
+        ((mg_game_phase * self.mg + eg_game_phase * self.eg) / MAX_GAME_PHASE) as Score
+    }
+}
+
+// Written as [mg, eg] in eval parameter files
+impl Serialize for TaperedScore {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        [self.mg, self.eg].serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TaperedScore {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let [mg, eg] = <[i32; 2]>::deserialize(deserializer)?;
+        Ok(Self::new(mg, eg))
+    }
+}
+
+impl Add for TaperedScore {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.mg + rhs.mg, self.eg + rhs.eg)
+    }
+}
+
+impl AddAssign for TaperedScore {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for TaperedScore {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.mg - rhs.mg, self.eg - rhs.eg)
+    }
+}
+
+impl SubAssign for TaperedScore {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl Neg for TaperedScore {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self::new(-self.mg, -self.eg)
+    }
+}
+
+impl Mul<i32> for TaperedScore {
+    type Output = Self;
+
+    fn mul(self, rhs: i32) -> Self::Output {
+        Self::new(self.mg * rhs, self.eg * rhs)
+    }
+}
@@ -0,0 +1,88 @@
+use super::{
+    Eval, TaperedScore, AttackMaps, Tracer,
+    super::{Board, Bitboard, Color, PieceType::{self, *}, precomputed, util}
+};
+
+// Per enemy piece (not pawn or king) attacked by a pawn
+pub(super) const THREAT_BY_PAWN: TaperedScore = TaperedScore::new(50, 35);
+// Per enemy rook or queen attacked by a knight or bishop, indexed by the piece type of the victim
+pub(super) const THREAT_BY_MINOR: [TaperedScore; 6] = [
+    TaperedScore::ZERO,
+    TaperedScore::ZERO,
+    TaperedScore::ZERO,
+    TaperedScore::new(35, 40),
+    TaperedScore::new(40, 45),
+    TaperedScore::ZERO,
+];
+// Per enemy queen attacked by a rook
+pub(super) const THREAT_BY_ROOK: TaperedScore = TaperedScore::new(40, 40);
+// Per attacked enemy piece (not pawn or king) that is not defended
+pub(super) const HANGING_PIECE: TaperedScore = TaperedScore::new(30, 20);
+// Per enemy piece that can be attacked by a safe pawn push
+pub(super) const PAWN_PUSH_THREAT: TaperedScore = TaperedScore::new(15, 12);
+
+impl Eval {
+    // Threats from white's perspective
+    pub(super) fn evaluate_threats(&self, board: &Board, attacks: &AttackMaps, trace: &mut impl Tracer) -> TaperedScore {
+        self.threats(board, attacks, Color::White, trace) - self.threats(board, attacks, Color::Black, trace)
+    }
+
+    // Threats made by the given color
+    fn threats(&self, board: &Board, attacks: &AttackMaps, color: Color, trace: &mut impl Tracer) -> TaperedScore {
+        let p = &self.params;
+        let enemy_piece = |pt: PieceType| board.bbs[PieceType::from_color(pt, -color) as usize];
+        let enemy_pieces = enemy_piece(WKnight) | enemy_piece(WBishop) | enemy_piece(WRook) | enemy_piece(WQueen);
+        if enemy_pieces == precomputed::EMPTY {
+            return TaperedScore::ZERO;
+        }
+
+        let own_attacks = attacks.by_color(color);
+        let enemy_attacks = attacks.by_color(-color);
+        let pawn_attacks = attacks.by_piece_type(PieceType::from_color(WPawn, color));
+        let minor_attacks = attacks.by_piece_type(PieceType::from_color(WKnight, color)) | attacks.by_piece_type(PieceType::from_color(WBishop, color));
+
+        let pawn_threats = (enemy_pieces & pawn_attacks).count_ones() as i32;
+        let mut score = p.threat_by_pawn * pawn_threats;
+        trace.add(color, |t| &mut t.threat_by_pawn, pawn_threats);
+
+        for pt in [WRook, WQueen] {
+            let minor_threats = (enemy_piece(pt) & minor_attacks).count_ones() as i32;
+            score += p.threat_by_minor[pt as usize] * minor_threats;
+            trace.add(color, |t| &mut t.threat_by_minor[pt as usize], minor_threats);
+        }
+        let rook_threats = (enemy_piece(WQueen) & attacks.by_piece_type(PieceType::from_color(WRook, color))).count_ones() as i32;
+        score += p.threat_by_rook * rook_threats;
+        trace.add(color, |t| &mut t.threat_by_rook, rook_threats);
+
+        let hanging_pieces = (enemy_pieces & own_attacks & !enemy_attacks).count_ones() as i32;
+        score += p.hanging_piece * hanging_pieces;
+        trace.add(color, |t| &mut t.hanging_piece, hanging_pieces);
+
+        // Squares a pawn can be pushed to without being captured by a pawn, or without being captured for free
+        let own_pawns = board.bbs[PieceType::from_color(WPawn, color) as usize];
+        let empty = !board.bbs[AnyPiece as usize];
+        let enemy_pawn_attacks = attacks.by_piece_type(PieceType::from_color(WPawn, -color));
+        let (single_pushes, double_pushes) = Self::pawn_pushes(own_pawns, empty, color);
+        let safe_pushes = (single_pushes | double_pushes)
+            & !enemy_pawn_attacks
+            & (own_attacks | !enemy_attacks);
+        let push_threats = (util::pawn_attacks(safe_pushes, color) & enemy_pieces & !pawn_attacks).count_ones() as i32;
+        score += p.pawn_push_threat * push_threats;
+        trace.add(color, |t| &mut t.pawn_push_threat, push_threats);
+
+        score
+    }
+
+    fn pawn_pushes(pawns: Bitboard, empty: Bitboard, color: Color) -> (Bitboard, Bitboard) {
+        match color {
+            Color::White => {
+                let single_pushes = (pawns << 8) & empty;
+                (single_pushes, ((single_pushes & precomputed::ROWS[2]) << 8) & empty)
+            },
+            Color::Black => {
+                let single_pushes = (pawns >> 8) & empty;
+                (single_pushes, ((single_pushes & precomputed::ROWS[5]) >> 8) & empty)
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use super::{Eval, EvalParams, TaperedScore, super::{Board, Color, precomputed, util}};
+
+/*
+    Records how often every eval weight is used, so the eval can be expressed as a linear function of the weights.
+    The eval functions are generic over the tracer, and the no-op tracer () compiles away completely.
+ */
+pub trait Tracer {
+    const ENABLED: bool;
+
+    // Adds count uses of a weight by the given color
+    fn add(&mut self, color: Color, weight: impl Fn(&mut EvalParams) -> &mut TaperedScore, count: i32);
+    // Terms that are not linear in the weights, from the perspective of the given color
+    fn add_constant(&mut self, color: Color, score: TaperedScore);
+    fn set_scale_factor(&mut self, scale_factor: i32);
+    fn set_endgame_evaluator(&mut self);
+}
+
+impl Tracer for () {
+    const ENABLED: bool = false;
+
+    #[inline(always)]
+    fn add(&mut self, _color: Color, _weight: impl Fn(&mut EvalParams) -> &mut TaperedScore, _count: i32) {}
+    #[inline(always)]
+    fn add_constant(&mut self, _color: Color, _score: TaperedScore) {}
+    #[inline(always)]
+    fn set_scale_factor(&mut self, _scale_factor: i32) {}
+    #[inline(always)]
+    fn set_endgame_evaluator(&mut self) {}
+}
+
+pub struct EvalTrace {
+    // Uses of every weight by white and black, stored in both mg and eg
+    pub counts: [EvalParams; 2],
+    pub constants: [TaperedScore; 2],
+    pub scale_factor: i32,
+    pub phase: i32,
+    // The position was scored by a dedicated endgame evaluator, which doesn't use the weights
+    pub endgame_evaluator: bool
+}
+
+impl EvalTrace {
+    pub fn new() -> Self {
+        let mut counts = EvalParams::DEFAULT;
+        for weight in counts.tapered_weights_mut() {
+            *weight = TaperedScore::ZERO;
+        }
+
+        Self {
+            counts: [counts.clone(), counts],
+            constants: [TaperedScore::ZERO; 2],
+            scale_factor: 0,
+            phase: 0,
+            endgame_evaluator: false
+        }
+    }
+}
+
+impl EvalTrace {
+    pub fn index(color: Color) -> usize {
+        match color {
+            Color::White => 0,
+            Color::Black => 1
+        }
+    }
+
+    // Uses by white minus uses by black, in the order of EvalParams::tapered_weights_mut
+    pub fn coefficients(&mut self) -> Vec<i32> {
+        let [white, black] = &mut self.counts;
+        white.tapered_weights_mut().into_iter().zip(black.tapered_weights_mut()).map(|(w, b)| w.mg - b.mg).collect()
+    }
+
+    // The constant terms from white's perspective
+    pub fn constant(&self) -> TaperedScore {
+        self.constants[0] - self.constants[1]
+    }
+}
+
+impl Default for EvalTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tracer for EvalTrace {
+    const ENABLED: bool = true;
+
+    fn add(&mut self, color: Color, weight: impl Fn(&mut EvalParams) -> &mut TaperedScore, count: i32) {
+        let weight = weight(&mut self.counts[Self::index(color)]);
+        weight.mg += count;
+        weight.eg = weight.mg;
+    }
+
+    fn add_constant(&mut self, color: Color, score: TaperedScore) {
+        self.constants[Self::index(color)] += score;
+    }
+
+    fn set_scale_factor(&mut self, scale_factor: i32) {
+        self.scale_factor = scale_factor;
+    }
+
+    fn set_endgame_evaluator(&mut self) {
+        self.endgame_evaluator = true;
+    }
+}
+
+impl Eval {
+    // The piece-square eval is updated incrementally in Board, so it is traced separately
+    pub(super) fn trace_psqt(board: &Board, trace: &mut impl Tracer) {
+        for pt in 0..6 {
+            for color in [Color::White, Color::Black] {
+                let mut pieces = board.bbs[pt + if color == Color::White {0} else {6}];
+                while pieces != precomputed::EMPTY {
+                    let sq = util::pop_ls1b(&mut pieces) as usize;
+                    // The tables are written with a8 first, so they are flipped for white
+                    let table_sq = if color == Color::White {sq ^ 56} else {sq};
+                    trace.add(color, |t| &mut t.piece_values[pt], 1);
+                    trace.add(color, |t| &mut t.piece_square_tables[pt][table_sq], 1);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,348 @@
+use std::{error::Error, fs::File, io::{BufRead, BufReader}, sync::Arc};
+
+use super::{
+    Eval, EvalParams, TaperedScore,
+    endgame::SCALE_FACTOR_NORMAL,
+    piece_square::MAX_GAME_PHASE,
+    super::{Board, MoveGenerator}
+};
+
+const INITIAL_K_STEP: f64 = 0.5;
+const MIN_K_STEP: f64 = 0.0001;
+
+// Adam optimizer settings
+const LEARNING_RATE: f64 = 1.0;
+const BETA1: f64 = 0.9;
+const BETA2: f64 = 0.999;
+const EPSILON: f64 = 1e-8;
+
+// Smallest probability used in the loss, to prevent ln(0)
+const MIN_PROBABILITY: f64 = 1e-9;
+
+// A position as a linear function of the weights, with the game result from white's perspective
+struct TuneEntry {
+    // Index into the weights and the number of uses (white minus black)
+    coefficients: Vec<(u16, i16)>,
+    constant: TaperedScore,
+    phase: f64,
+    scale: f64,
+    result: f64
+}
+
+/*
+    Texel tuning: fits the tapered eval weights to game results.
+    The win probability of an eval is sigmoid(k * eval / 400), where k is fitted first.
+    The weights are then optimized with Adam on the logistic (cross-entropy) loss.
+    The integer king safety weights and the endgame evaluator formulas are kept as they are.
+ */
+pub struct Tuner {
+    entries: Vec<TuneEntry>,
+    weights: Vec<[f64; 2]>,
+    k: f64,
+    // The weights that are not tuned are taken from these
+    params: EvalParams
+}
+
+impl Tuner {
+    /*
+        Reads positions with their game results, one per line. Accepted formats:
+        'fen c9 "1-0";', 'fen [1.0]', 'fen, 0.5' and 'fen | 0'. Results are from white's perspective.
+        Tuning starts from the given weights.
+     */
+    pub fn load(path: &str, params: &EvalParams) -> Result<Self, Box<dyn Error>> {
+        let mut params = params.clone();
+        let weights = params.tapered_weights_mut().iter().map(|w| [w.mg as f64, w.eg as f64]).collect::<Vec<[f64; 2]>>();
+
+        let mut eval = Eval::with_params(Arc::new(params.clone()));
+        let mg = MoveGenerator::new();
+        let mut entries = Vec::new();
+        let mut skipped = 0;
+
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            let Some((mut board, result)) = Self::parse_line(&line) else {
+                if !line.trim().is_empty() {
+                    skipped += 1;
+                }
+                continue;
+            };
+            board.refresh_psqt(eval.params().clone());
+
+            let mut trace = eval.trace(&board, &mg);
+            let coefficients = trace.coefficients().into_iter().enumerate()
+                .filter(|&(_, count)| count != 0)
+                .map(|(i, count)| (i as u16, count as i16))
+                .collect();
+            entries.push(TuneEntry {
+                coefficients,
+                constant: trace.constant(),
+                phase: trace.phase as f64 / MAX_GAME_PHASE as f64,
+                scale: trace.scale_factor as f64 / SCALE_FACTOR_NORMAL as f64,
+                result
+            });
+        }
+
+        if skipped > 0 {
+            println!("Skipped {} invalid lines", skipped);
+        }
+        if entries.is_empty() {
+            return Err("No positions found".into());
+        }
+
+        Ok(Self { entries, weights, k: 1.0, params })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn parse_line(line: &str) -> Option<(Board, f64)> {
+        let line = line.trim();
+        let (fen, result) = if let Some(start) = line.find('[') {
+            (&line[..start], line[start + 1..].split(']').next()?.trim().parse::<f64>().ok()?)
+        } else if let Some(sep) = line.rfind([',', '|']) {
+            (&line[..sep], line[sep + 1..].trim().parse::<f64>().ok()?)
+        } else {
+            let result = ["1-0", "0-1", "1/2-1/2"].iter().position(|r| line.contains(r))?;
+            let fen_end = line.find(" c9").or_else(|| line.find(';')).unwrap_or(line.len());
+            (&line[..fen_end], [1.0, 0.0, 0.5][result])
+        };
+
+        // EPD positions have no move counters
+        if !matches!(fen.split_ascii_whitespace().count(), 4 | 6) {
+            return None;
+        }
+        Some((Board::try_from_fen(fen).ok()?, result))
+    }
+
+    fn evaluate(weights: &[[f64; 2]], entry: &TuneEntry) -> f64 {
+        let (mut mg, mut eg) = (entry.constant.mg as f64, entry.constant.eg as f64);
+        for &(i, count) in &entry.coefficients {
+            mg += weights[i as usize][0] * count as f64;
+            eg += weights[i as usize][1] * count as f64;
+        }
+        mg * entry.phase + eg * entry.scale * (1.0 - entry.phase)
+    }
+
+    fn sigmoid(k: f64, eval: f64) -> f64 {
+        1.0 / (1.0 + 10f64.powf(-k * eval / 400.0))
+    }
+
+    fn loss(&self, k: f64) -> f64 {
+        self.entries.iter().map(|entry| {
+            let p = Self::sigmoid(k, Self::evaluate(&self.weights, entry)).clamp(MIN_PROBABILITY, 1.0 - MIN_PROBABILITY);
+            -(entry.result * p.ln() + (1.0 - entry.result) * (1.0 - p).ln())
+        }).sum::<f64>() / self.entries.len() as f64
+    }
+
+    // Finds the k that fits the current weights best, with the same local search as the WDL model
+    pub fn fit_k(&mut self) -> f64 {
+        let mut best_loss = self.loss(self.k);
+        let mut step = INITIAL_K_STEP;
+
+        while step >= MIN_K_STEP {
+            let mut improved = false;
+            for delta in [step, -step] {
+                let k = self.k + delta;
+                if k <= 0.0 {
+                    continue;
+                }
+                let loss = self.loss(k);
+                if loss < best_loss {
+                    best_loss = loss;
+                    self.k = k;
+                    improved = true;
+                    break;
+                }
+            }
+            if !improved {
+                step /= 2.0;
+            }
+        }
+
+        self.k
+    }
+
+    fn gradient(&self) -> Vec<[f64; 2]> {
+        let mut gradient = vec![[0.0; 2]; self.weights.len()];
+        let k = self.k * std::f64::consts::LN_10 / 400.0;
+
+        for entry in &self.entries {
+            // The derivative of the logistic loss with respect to the eval
+            let error = (Self::sigmoid(self.k, Self::evaluate(&self.weights, entry)) - entry.result) * k;
+            let mg = error * entry.phase;
+            let eg = error * entry.scale * (1.0 - entry.phase);
+            for &(i, count) in &entry.coefficients {
+                gradient[i as usize][0] += mg * count as f64;
+                gradient[i as usize][1] += eg * count as f64;
+            }
+        }
+
+        let n = self.entries.len() as f64;
+        gradient.iter_mut().flatten().for_each(|g| *g /= n);
+        gradient
+    }
+
+    // Runs the given number of full-batch gradient descent steps, printing the loss every report_interval epochs
+    pub fn tune(&mut self, epochs: usize, report_interval: usize) {
+        let mut m = vec![[0.0; 2]; self.weights.len()];
+        let mut v = vec![[0.0; 2]; self.weights.len()];
+
+        for epoch in 1..=epochs {
+            let gradient = self.gradient();
+            for i in 0..self.weights.len() {
+                for j in 0..2 {
+                    m[i][j] = BETA1 * m[i][j] + (1.0 - BETA1) * gradient[i][j];
+                    v[i][j] = BETA2 * v[i][j] + (1.0 - BETA2) * gradient[i][j] * gradient[i][j];
+                    let m_hat = m[i][j] / (1.0 - BETA1.powi(epoch as i32));
+                    let v_hat = v[i][j] / (1.0 - BETA2.powi(epoch as i32));
+                    self.weights[i][j] -= LEARNING_RATE * m_hat / (v_hat.sqrt() + EPSILON);
+                }
+            }
+
+            if epoch % report_interval.max(1) == 0 || epoch == epochs {
+                println!("Epoch {}: loss {:.6}", epoch, self.loss(self.k));
+            }
+        }
+    }
+
+    // The current eval weights, with the tuned weights rounded
+    pub fn params(&self) -> EvalParams {
+        let mut params = self.params.clone();
+        for (weight, tuned) in params.tapered_weights_mut().into_iter().zip(&self.weights) {
+            *weight = TaperedScore::new(tuned[0].round() as i32, tuned[1].round() as i32);
+        }
+        params.update_pqts();
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use super::super::super::{Move, MoveList};
+
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    // Positions from random games, and a few that are scored by the endgame evaluators
+    fn random_fens(n: usize, seed: u64) -> Vec<String> {
+        let mg = MoveGenerator::new();
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut fens = vec![
+            String::from("8/8/8/4k3/8/3K4/8/5BN1 w - - 0 1"),
+            String::from("1R6/8/4K3/8/8/4k3/8/8 b - - 0 1"),
+            String::from("7k/8/8/8/2p5/8/2K5/7R w - - 0 1"),
+            String::from("7K/8/8/8/8/8/2pk4/R7 w - - 0 1")
+        ];
+        while fens.len() < n {
+            let mut board = Board::try_from_fen(START_FEN).unwrap();
+            for _ in 0..rng.gen_range(4..80) {
+                let mut moves = MoveList::new();
+                mg.generate_legal_moves(&mut board, &mut moves, false);
+                let moves = moves.collect::<Vec<Move>>();
+                if moves.is_empty() {
+                    break;
+                }
+                board.make_move(&moves[rng.gen_range(0..moves.len())]);
+            }
+            fens.push(board.get_fen());
+        }
+        fens
+    }
+
+    fn load(name: &str, lines: &[String]) -> Tuner {
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, lines.join("\n")).unwrap();
+        let tuner = Tuner::load(path.to_str().unwrap(), &EvalParams::DEFAULT).unwrap();
+        assert_eq!(tuner.len(), lines.len());
+        tuner
+    }
+
+    #[test]
+    fn parse_line() {
+        let epd = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -";
+        let key = Board::try_from_fen(epd).unwrap().key;
+        let lines = [
+            (format!("{} c9 \"1-0\";", epd), 1.0),
+            (format!("{} 0 1 [0.5]", epd), 0.5),
+            (format!("{} 0 1, 0.5", epd), 0.5),
+            (format!("{} | 0", epd), 0.0),
+            (format!("{} c9 \"0-1\";", epd), 0.0),
+            (format!("{} [1.0]", epd), 1.0)
+        ];
+        for (line, result) in lines {
+            let (board, parsed) = Tuner::parse_line(&line).unwrap();
+            assert_eq!((board.key, parsed), (key, result), "{}", line);
+        }
+
+        for line in ["", "garbage [1.0]", epd, "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 [1.0]"] {
+            assert!(Tuner::parse_line(line).is_none(), "{}", line);
+        }
+    }
+
+    // The linear model reproduces the eval, also for the endgame evaluators and king safety
+    #[test]
+    fn entries_match_eval() {
+        let fens = random_fens(200, 1);
+        let tuner = load("peripheral_tuner_entries.epd", &fens.iter().map(|fen| format!("{} [0.5]", fen)).collect::<Vec<String>>());
+
+        let mg = MoveGenerator::new();
+        let mut eval = Eval::new();
+        let mut king_safety = 0;
+        for (fen, entry) in fens.iter().zip(&tuner.entries) {
+            let board = Board::try_from_fen(fen).unwrap();
+            let score = eval.eval(&board, &mg) as f64;
+            assert!((Tuner::evaluate(&tuner.weights, entry) - score).abs() <= 1.0, "{} {} {}", fen, Tuner::evaluate(&tuner.weights, entry), score);
+            king_safety += eval.breakdown(&board, &mg).terms.iter().any(|term| term.name == "King safety" && term.total().mg != 0) as i32;
+        }
+        assert!(king_safety > 0);
+    }
+
+    #[test]
+    fn fit_k_and_gradient() {
+        let fens = random_fens(200, 2);
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let lines = fens.iter().map(|fen| format!("{} [{}]", fen, [0.0, 0.5, 1.0][rng.gen_range(0..3)])).collect::<Vec<String>>();
+        let mut tuner = load("peripheral_tuner_gradient.epd", &lines);
+
+        // The gradient is the derivative of the loss
+        tuner.k = 1.3;
+        let gradient = tuner.gradient();
+        for i in [0, 1, 64, 500, tuner.weights.len() - 1] {
+            for (j, &analytical) in gradient[i].iter().enumerate() {
+                let h = 0.01;
+                let mut shifted_loss = |delta: f64| {
+                    tuner.weights[i][j] += delta;
+                    let loss = tuner.loss(tuner.k);
+                    tuner.weights[i][j] -= delta;
+                    loss
+                };
+                let numerical = (shifted_loss(h) - shifted_loss(-h)) / (2.0 * h);
+                assert!((numerical - analytical).abs() < 1e-6, "{} {} {} {}", i, j, numerical, analytical);
+            }
+        }
+
+        // Tuning lowers the loss
+        tuner.fit_k();
+        let loss = tuner.loss(tuner.k);
+        tuner.tune(20, 20);
+        assert!(tuner.loss(tuner.k) < loss);
+
+        // With results that follow the current eval exactly, fit_k finds that k and the weights are at the optimum
+        let mut tuner = load("peripheral_tuner_optimum.epd", &fens.iter().map(|fen| format!("{} [0.5]", fen)).collect::<Vec<String>>());
+        for entry in &mut tuner.entries {
+            entry.result = Tuner::sigmoid(0.8, Tuner::evaluate(&tuner.weights, entry));
+        }
+        assert!((tuner.fit_k() - 0.8).abs() < 0.001);
+        assert!(tuner.gradient().iter().flatten().all(|g| g.abs() < 1e-4));
+    }
+}
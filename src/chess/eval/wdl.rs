@@ -0,0 +1,128 @@
+use super::super::{Score, CHECKMATE_SCORE};
+use super::piece_square::MAX_GAME_PHASE;
+
+const PERMILLE: f64 = 1000.0;
+
+// Smallest probability used in the log-likelihood, to prevent ln(0)
+const MIN_PROBABILITY: f64 = 1e-6;
+const INITIAL_FIT_STEP: f64 = 8.0;
+const MIN_FIT_STEP: f64 = 0.01;
+
+/*
+    Win/draw/loss model, based on Stockfish's win_rate_model:
+    win_rate(score) = 1 / (1 + exp((a - score) / b))
+    where a and b are third degree polynomials in the game phase (0 = endgame, 1 = opening).
+
+    a is the score at which the side to move wins half of its games,
+    b describes how fast the win rate increases with the score.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct WdlModel {
+    pub a: [f64; 4],
+    pub b: [f64; 4]
+}
+
+// A score from the perspective of the side to move, with the result for that side (1 = win, 0.5 = draw, 0 = loss)
+pub struct WdlSample {
+    pub score: Score,
+    pub phase: i32,
+    pub result: f64
+}
+
+impl WdlModel {
+    pub const fn new() -> Self {
+        Self {
+            a: [140.0, 60.0, 0.0, 0.0],
+            b: [50.0, 30.0, 0.0, 0.0]
+        }
+    }
+
+    fn polynomial(coefficients: &[f64; 4], x: f64) -> f64 {
+        coefficients.iter().rev().fold(0.0, |acc, c| acc * x + c)
+    }
+
+    fn win_rate(&self, score: Score, phase: i32) -> f64 {
+        let x = phase.clamp(0, MAX_GAME_PHASE) as f64 / MAX_GAME_PHASE as f64;
+        let a = Self::polynomial(&self.a, x);
+        let b = Self::polynomial(&self.b, x).max(1.0);
+        1.0 / (1.0 + ((a - score as f64) / b).exp())
+    }
+
+    fn probabilities(&self, score: Score, phase: i32) -> (f64, f64, f64) {
+        let win = self.win_rate(score, phase);
+        let loss = self.win_rate(-score, phase);
+        (win, (1.0 - win - loss).max(0.0), loss)
+    }
+
+    // Win, draw and loss permille for the side to move
+    pub fn wdl(&self, score: Score, phase: i32) -> (u16, u16, u16) {
+        if CHECKMATE_SCORE - score.abs() < 100 { // mate score
+            return if score > 0 {(1000, 0, 0)} else {(0, 0, 1000)};
+        }
+
+        let (win, _, loss) = self.probabilities(score, phase);
+        let win = (win * PERMILLE).round() as u16;
+        let loss = std::cmp::min((loss * PERMILLE).round() as u16, 1000 - win);
+        (win, 1000 - win - loss, loss)
+    }
+
+    fn log_loss(&self, samples: &[WdlSample]) -> f64 {
+        samples.iter().map(|sample| {
+            let (win, draw, loss) = self.probabilities(sample.score, sample.phase);
+            let p = if sample.result > 0.75 {
+                win
+            } else if sample.result < 0.25 {
+                loss
+            } else {
+                draw
+            };
+            -p.max(MIN_PROBABILITY).ln()
+        }).sum::<f64>() / samples.len().max(1) as f64
+    }
+
+    /*
+        Calibrate the model on (self-play) results by minimizing the log loss of the observed outcomes.
+        Uses a simple local search: every coefficient is nudged in both directions, and the step size
+        is halved once no nudge improves the fit anymore.
+     */
+    pub fn fit(&self, samples: &[WdlSample]) -> Self {
+        let mut model = *self;
+        let mut best_loss = model.log_loss(samples);
+        let mut step = INITIAL_FIT_STEP;
+
+        while step >= MIN_FIT_STEP {
+            let mut improved = false;
+
+            for i in 0..8 {
+                for delta in [step, -step] {
+                    let mut candidate = model;
+                    if i < 4 {
+                        candidate.a[i] += delta;
+                    } else {
+                        candidate.b[i - 4] += delta;
+                    }
+
+                    let loss = candidate.log_loss(samples);
+                    if loss < best_loss {
+                        best_loss = loss;
+                        model = candidate;
+                        improved = true;
+                        break;
+                    }
+                }
+            }
+
+            if !improved {
+                step /= 2.0;
+            }
+        }
+
+        model
+    }
+}
+
+impl Default for WdlModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,7 +1,12 @@
 use super::{Board, MoveList, precomputed, util, PieceType::{*, self}, Color::*, Bitboard, Square, Move, CastlingFlags};
 
+use std::sync::OnceLock;
+
 mod magics;
 
+// Lookup tables for code that only generates moves now and then, like SAN conversion
+static SHARED: OnceLock<MoveGenerator> = OnceLock::new();
+
 enum SpecialBitsContainer {
     NormalMove,
     MayPromote(Move),
@@ -25,6 +30,10 @@ impl MoveGenerator {
         mg
     }
 
+    pub fn shared() -> &'static Self {
+        SHARED.get_or_init(Self::new)
+    }
+
     #[inline(always)]
     pub fn generate_legal_moves(&self, b: &mut Board, moves: &mut MoveList, quiescence: bool) {
         let (check_mask, king_ban) = self.generate_check_mask_and_king_ban(b);
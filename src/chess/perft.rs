@@ -65,6 +65,8 @@ impl Perft {
     #[inline(always)]
     pub fn perft(&mut self, depth: u8, root: bool, debug: bool) -> u64 {
         debug_assert!(self.board.key == self.board.make_key());
+        debug_assert!(self.board.pawn_key == self.board.make_pawn_key());
+        debug_assert!((self.board.mg_score, self.board.eg_score, self.board.game_phase) == self.board.make_psqt());
 
         let mut moves = MoveList::new();
         self.mg.generate_legal_moves(&mut self.board, &mut moves, false);
@@ -98,6 +100,8 @@ impl Perft {
     #[inline(always)]
     pub fn hash_perft(&mut self, depth: u8, root: bool, debug: bool) -> u64 {
         debug_assert!(self.board.key == self.board.make_key());
+        debug_assert!(self.board.pawn_key == self.board.make_pawn_key());
+        debug_assert!((self.board.mg_score, self.board.eg_score, self.board.game_phase) == self.board.make_psqt());
         let idx = (self.board.key & PERFT_TT_MASK) as usize ^ depth as usize;
 
         if self.tt[idx].key == self.board.key {
@@ -0,0 +1,90 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use super::{Board, Move};
+
+mod reader;
+pub use reader::PgnReader;
+
+mod writer;
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameResult {
+    WhiteWin,
+    BlackWin,
+    Draw,
+    Unknown
+}
+
+impl FromStr for GameResult {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1-0" => Ok(Self::WhiteWin),
+            "0-1" => Ok(Self::BlackWin),
+            "1/2-1/2" => Ok(Self::Draw),
+            "*" => Ok(Self::Unknown),
+            other => Err(format!("Invalid result: {}", other).into())
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::WhiteWin => "1-0",
+            Self::BlackWin => "0-1",
+            Self::Draw => "1/2-1/2",
+            Self::Unknown => "*"
+        })
+    }
+}
+
+// A move with its annotations. The variations are alternatives to this move, from the same position
+#[derive(Clone, Debug)]
+pub struct MoveNode {
+    pub mv: Move,
+    pub san: String,
+    pub nags: Vec<u8>,
+    // Comment after the move
+    pub comment: Option<String>,
+    pub variations: Vec<Line>
+}
+
+impl MoveNode {
+    pub fn new(mv: Move, san: String) -> Self {
+        Self { mv, san, nags: Vec::new(), comment: None, variations: Vec::new() }
+    }
+}
+
+// A sequence of moves, with the comment before the first one
+#[derive(Clone, Debug, Default)]
+pub struct Line {
+    pub comment: Option<String>,
+    pub moves: Vec<MoveNode>
+}
+
+#[derive(Clone, Debug)]
+pub struct Game {
+    // Tag pairs in the order of the file
+    pub headers: Vec<(String, String)>,
+    pub start_fen: String,
+    pub mainline: Line,
+    pub result: GameResult
+}
+
+impl Game {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn start_board(&self) -> Result<Board, Box<dyn Error>> {
+        Board::try_from_fen(&self.start_fen)
+    }
+
+    pub fn mainline_moves(&self) -> impl Iterator<Item = Move> + '_ {
+        self.mainline.moves.iter().map(|node| node.mv)
+    }
+}
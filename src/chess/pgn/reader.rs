@@ -0,0 +1,271 @@
+use std::{error::Error, fs::File, io::{self, BufRead, BufReader}, vec::IntoIter};
+
+use super::{
+    Game, GameResult, Line, MoveNode, START_FEN,
+    super::{Board, Move, MoveGenerator}
+};
+
+// Move suffix annotations and their NAGs
+const SUFFIX_NAGS: [(&str, u8); 6] = [("!!", 3), ("??", 4), ("!?", 5), ("?!", 6), ("!", 1), ("?", 2)];
+
+enum Token<'a> {
+    Move(&'a str),
+    Nag(u8),
+    Comment(&'a str),
+    VariationStart,
+    VariationEnd,
+    Result(GameResult)
+}
+
+/*
+    Reads the games of a PGN file one by one, so large databases don't have to fit in memory.
+    Every game is replayed on a board to validate its moves. An invalid game is returned as an error,
+    and reading continues with the next game.
+ */
+pub struct PgnReader<R: BufRead> {
+    lines: io::Lines<R>,
+    // First tag line of the next game
+    pending: Option<String>
+}
+
+impl PgnReader<BufReader<File>> {
+    pub fn open(path: &str) -> io::Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { lines: reader.lines(), pending: None }
+    }
+
+    fn parse_game(tag_lines: &[String], movetext: &str) -> Result<Game, Box<dyn Error>> {
+        let headers = tag_lines.iter().filter_map(|line| parse_tag(line)).collect::<Vec<(String, String)>>();
+        let start_fen = headers.iter()
+            .find(|(tag, _)| tag == "FEN")
+            .map_or(START_FEN.to_string(), |(_, fen)| fen.clone());
+
+        let mut board = Board::try_from_fen(&start_fen).map_err(|e| format!("Invalid FEN tag '{}': {}", start_fen, e))?;
+        let mut tokens = tokenize(movetext)?.into_iter();
+        let mut result = None;
+        let mainline = parse_line(&mut tokens, &mut board, MoveGenerator::shared(), 0, 0, &mut result)?;
+
+        let result = match result {
+            Some(result) => result,
+            None => headers.iter().find(|(tag, _)| tag == "Result").and_then(|(_, result)| result.parse().ok()).unwrap_or(GameResult::Unknown)
+        };
+        Ok(Game { headers, start_fen, mainline, result })
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<Game, Box<dyn Error>>;
+
+    // A game ends at the first tag line after its movetext, or at the end of the input
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut tag_lines = Vec::new();
+        let mut movetext = String::new();
+        let mut in_comment = false;
+
+        loop {
+            let line = match self.pending.take() {
+                Some(line) => line,
+                None => match self.lines.next() {
+                    Some(Ok(line)) => line,
+                    Some(Err(e)) => return Some(Err(e.into())),
+                    None => break
+                }
+            };
+            let line = line.trim_start_matches('\u{feff}').trim();
+
+            if !in_comment {
+                // Escape mechanism for other formats
+                if line.starts_with('%') {
+                    continue;
+                }
+                if line.starts_with('[') {
+                    if !movetext.trim().is_empty() {
+                        self.pending = Some(line.to_string());
+                        break;
+                    }
+                    tag_lines.push(line.to_string());
+                    continue;
+                }
+            }
+
+            in_comment = ends_in_comment(line, in_comment);
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+
+        if tag_lines.is_empty() && movetext.trim().is_empty() {
+            return None;
+        }
+        Some(Self::parse_game(&tag_lines, &movetext))
+    }
+}
+
+// [Name "Value"], with \" and \\ escapes in the value
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.trim_end().strip_suffix(']')?;
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        unescaped.push(if c == '\\' { chars.next().unwrap_or('\\') } else { c });
+    }
+    Some((name.to_string(), unescaped))
+}
+
+// Whether a brace comment is still open at the end of the line
+fn ends_in_comment(line: &str, mut in_comment: bool) -> bool {
+    for c in line.chars() {
+        match c {
+            '}' if in_comment => in_comment = false,
+            '{' if !in_comment => in_comment = true,
+            ';' if !in_comment => break,
+            _ => ()
+        }
+    }
+    in_comment
+}
+
+fn tokenize(movetext: &str) -> Result<Vec<Token<'_>>, Box<dyn Error>> {
+    let mut tokens = Vec::new();
+    let mut rest = movetext;
+
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '{' => {
+                let end = rest.find('}').ok_or("Unterminated comment")?;
+                tokens.push(Token::Comment(rest[1..end].trim()));
+                rest = &rest[end + 1..];
+            },
+            ';' => {
+                let end = rest.find('\n').unwrap_or(rest.len());
+                tokens.push(Token::Comment(rest[1..end].trim()));
+                rest = &rest[end..];
+            },
+            '(' => {
+                tokens.push(Token::VariationStart);
+                rest = &rest[1..];
+            },
+            ')' => {
+                tokens.push(Token::VariationEnd);
+                rest = &rest[1..];
+            },
+            c if c.is_whitespace() => rest = &rest[c.len_utf8()..],
+            _ => {
+                let end = rest.find(|c: char| c.is_whitespace() || "{};()".contains(c)).unwrap_or(rest.len());
+                push_symbol(&rest[..end], &mut tokens)?;
+                rest = &rest[end..];
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// A NAG, a result, or a move with an optional move number in front and annotations behind it
+fn push_symbol<'a>(symbol: &'a str, tokens: &mut Vec<Token<'a>>) -> Result<(), Box<dyn Error>> {
+    if let Some(nag) = symbol.strip_prefix('$') {
+        tokens.push(Token::Nag(nag.parse().map_err(|_| format!("Invalid NAG: {}", symbol))?));
+        return Ok(());
+    }
+    if let Ok(result) = symbol.parse::<GameResult>() {
+        tokens.push(Token::Result(result));
+        return Ok(());
+    }
+
+    // Move numbers like '12.' and '12...', possibly attached to the move
+    let symbol = match symbol.rfind('.') {
+        Some(i) if symbol[..i].trim_end_matches('.').chars().all(|c| c.is_ascii_digit()) => &symbol[i + 1..],
+        _ => symbol
+    };
+    if symbol.is_empty() {
+        return Ok(());
+    }
+
+    let san = symbol.trim_end_matches(['!', '?']);
+    tokens.push(Token::Move(san));
+    let suffix = &symbol[san.len()..];
+    if !suffix.is_empty() {
+        let nag = SUFFIX_NAGS.iter().find(|(s, _)| *s == suffix).ok_or(format!("Invalid annotation: {}", symbol))?.1;
+        tokens.push(Token::Nag(nag));
+    }
+    Ok(())
+}
+
+/*
+    Replays the moves of a line on the board, with its variations. Nested lines end at their closing parenthesis,
+    and leave the board as they found it.
+ */
+fn parse_line(
+    tokens: &mut IntoIter<Token<'_>>,
+    board: &mut Board,
+    mg: &MoveGenerator,
+    depth: usize,
+    start_ply: usize,
+    result: &mut Option<GameResult>
+) -> Result<Line, Box<dyn Error>> {
+    let mut line = Line::default();
+    let mut made = Vec::new();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Move(san) => {
+                let ply = start_ply + made.len();
+                let mv = Move::from_san_with(san, board, mg)
+                    .map_err(|e| format!("{} (ply {})", e, ply + 1))?;
+                line.moves.push(MoveNode::new(mv, mv.to_san_with(board, mg)));
+                board.make_move(&mv);
+                made.push(mv);
+            },
+            Token::Nag(nag) => {
+                if let Some(node) = line.moves.last_mut() {
+                    node.nags.push(nag);
+                }
+            },
+            Token::Comment(comment) => {
+                let target = match line.moves.last_mut() {
+                    Some(node) => &mut node.comment,
+                    None => &mut line.comment
+                };
+                match target {
+                    Some(existing) => {
+                        existing.push(' ');
+                        existing.push_str(comment);
+                    },
+                    None => *target = Some(comment.to_string())
+                }
+            },
+            Token::VariationStart => {
+                let last = *made.last().ok_or("Variation before the first move")?;
+                board.undo_move(&last);
+                let variation = parse_line(tokens, board, mg, depth + 1, start_ply + made.len() - 1, result)?;
+                board.make_move(&last);
+                line.moves.last_mut().expect("Line should have a move").variations.push(variation);
+            },
+            Token::VariationEnd => {
+                if depth == 0 {
+                    return Err("Unmatched ')'".into());
+                }
+                made.iter().rev().for_each(|mv| board.undo_move(mv));
+                return Ok(line);
+            },
+            Token::Result(game_result) => {
+                if depth > 0 {
+                    return Err("Result inside a variation".into());
+                }
+                *result = Some(game_result);
+                break;
+            }
+        }
+    }
+
+    if depth > 0 {
+        return Err("Unterminated variation".into());
+    }
+    Ok(line)
+}
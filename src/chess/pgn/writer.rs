@@ -0,0 +1,171 @@
+use std::{error::Error, fmt};
+
+use super::{
+    Game, GameResult, Line, MoveNode, START_FEN,
+    super::{Board, Color, Move, MoveGenerator, MoveList, Score, util}
+};
+
+// Maximum line length of the export format
+const MAX_LINE_LENGTH: usize = 80;
+
+// Tags that are always written, in this order
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"), ("Site", "?"), ("Date", "????.??.??"), ("Round", "?"), ("White", "?"), ("Black", "?"), ("Result", "*")
+];
+
+impl Game {
+    /*
+        A game without headers from a sequence of legal moves, which may also come from coordinate notation. The result is set when the game ended
+        in checkmate, stalemate, threefold repetition or by the fifty move rule.
+     */
+    pub fn from_moves(start_fen: &str, moves: &[Move]) -> Result<Self, Box<dyn Error>> {
+        let mut board = Board::try_from_fen(start_fen)?;
+        let mg = MoveGenerator::shared();
+        let mut mainline = Line::default();
+
+        for &mv in moves {
+            // Moves parsed from coordinate notation have no capture flag, so they are matched by their name
+            let mut legal_moves = MoveList::new();
+            mg.generate_legal_moves(&mut board, &mut legal_moves, false);
+            let name = mv.to_string();
+            let mv = legal_moves.find(|legal| legal.to_string() == name).ok_or(format!("Illegal move: {}", name))?;
+            mainline.moves.push(MoveNode::new(mv, mv.to_san_with(&mut board, mg)));
+            board.make_move(&mv);
+        }
+
+        let mut legal_moves = MoveList::new();
+        mg.generate_legal_moves(&mut board, &mut legal_moves, false);
+        let result = if *legal_moves.get_count() == 0 {
+            match (board.gs.is_in_check, board.gs.player_to_move) {
+                (false, _) => GameResult::Draw,
+                (true, Color::White) => GameResult::BlackWin,
+                (true, Color::Black) => GameResult::WhiteWin
+            }
+        } else if board.key_history.contains_3fold() || board.key_history.halfmove_clock() >= 100 {
+            GameResult::Draw
+        } else {
+            GameResult::Unknown
+        };
+
+        Ok(Self { headers: Vec::new(), start_fen: start_fen.trim().to_string(), mainline, result })
+    }
+
+    // Replaces the value of an existing tag, or adds it
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        match self.headers.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, existing)) => *existing = value.to_string(),
+            None => self.headers.push((name.to_string(), value.to_string()))
+        }
+    }
+
+    // Side to move and fullmove number of the starting position
+    fn start_move_number(&self) -> (bool, usize) {
+        let mut fields = self.start_fen.split_ascii_whitespace().skip(1);
+        let white_to_move = fields.next() != Some("b");
+        let fullmove = fields.nth(3).and_then(|n| n.parse().ok()).unwrap_or(1).max(1);
+        (white_to_move, fullmove)
+    }
+}
+
+impl MoveNode {
+    // Adds an engine eval comment like '[%eval 0.35,12]' or '[%eval #-3,20]'. The score is from white's perspective
+    pub fn add_eval(&mut self, score: Score, depth: u8) {
+        let eval = match util::mate_in_moves(score) {
+            Some(mate) => format!("[%eval #{},{}]", mate, depth),
+            None => format!("[%eval {:.2},{}]", score as f32 / 100.0, depth)
+        };
+        self.comment = Some(match self.comment.take() {
+            Some(comment) => format!("{} {}", eval, comment),
+            None => eval
+        });
+    }
+}
+
+// The words of a comment, in braces
+fn push_comment(comment: &str, tokens: &mut Vec<String>) {
+    let mut words = comment.split_ascii_whitespace().map(String::from).collect::<Vec<String>>();
+    if words.is_empty() {
+        tokens.push(String::from("{}"));
+        return;
+    }
+    words[0].insert(0, '{');
+    words.last_mut().expect("Comment should have words").push('}');
+    tokens.extend(words);
+}
+
+// ply counts from the white move of the first fullmove
+fn push_line(line: &Line, ply: usize, first_move_number: usize, tokens: &mut Vec<String>) {
+    if let Some(comment) = &line.comment {
+        push_comment(comment, tokens);
+    }
+
+    let mut needs_number = true;
+    for (i, node) in line.moves.iter().enumerate() {
+        let ply = ply + i;
+        let move_number = first_move_number + ply / 2;
+        if ply.is_multiple_of(2) {
+            tokens.push(format!("{}.", move_number));
+        } else if needs_number {
+            tokens.push(format!("{}...", move_number));
+        }
+        tokens.push(node.san.clone());
+        tokens.extend(node.nags.iter().map(|nag| format!("${}", nag)));
+        needs_number = false;
+
+        if let Some(comment) = &node.comment {
+            push_comment(comment, tokens);
+            needs_number = true;
+        }
+        for variation in node.variations.iter().filter(|variation| !variation.moves.is_empty()) {
+            let start = tokens.len();
+            push_line(variation, ply, first_move_number, tokens);
+            tokens[start].insert(0, '(');
+            tokens.last_mut().expect("Variation should have tokens").push(')');
+            needs_number = true;
+        }
+    }
+}
+
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let result = self.result.to_string();
+        for (tag, default) in SEVEN_TAG_ROSTER {
+            let value = if tag == "Result" { result.as_str() } else { self.header(tag).unwrap_or(default) };
+            writeln!(f, "[{} \"{}\"]", tag, escape_tag_value(value))?;
+        }
+        if self.start_fen != START_FEN {
+            writeln!(f, "[SetUp \"1\"]")?;
+            writeln!(f, "[FEN \"{}\"]", escape_tag_value(&self.start_fen))?;
+        }
+        for (tag, value) in &self.headers {
+            if !SEVEN_TAG_ROSTER.iter().any(|(roster_tag, _)| roster_tag == tag) && tag != "SetUp" && tag != "FEN" {
+                writeln!(f, "[{} \"{}\"]", tag, escape_tag_value(value))?;
+            }
+        }
+        writeln!(f)?;
+
+        let (white_to_move, first_move_number) = self.start_move_number();
+        let mut tokens = Vec::new();
+        push_line(&self.mainline, if white_to_move {0} else {1}, first_move_number, &mut tokens);
+        tokens.push(result);
+
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+                writeln!(f)?;
+                line_length = 0;
+            }
+            if line_length > 0 {
+                write!(f, " ")?;
+                line_length += 1;
+            }
+            write!(f, "{}", token)?;
+            line_length += token.len();
+        }
+        writeln!(f)
+    }
+}
@@ -28,6 +28,22 @@ pub const BISHOP_MOVES_NO_BORDER: [Bitboard; 64] = merge_slider_rays(
 
 pub const BETWEEN_BITBOARDS: [[Bitboard; 64]; 64] = precompute_between_bitboards();
 
+pub const FILES: [Bitboard; 8] = precompute_files();
+pub const ROWS: [Bitboard; 8] = precompute_rows();
+pub const ADJACENT_FILES: [Bitboard; 8] = precompute_adjacent_files();
+
+// All rows in front of a row, from the perspective of white or black
+pub const WHITE_FORWARD_ROWS: [Bitboard; 8] = precompute_white_forward_rows();
+pub const BLACK_FORWARD_ROWS: [Bitboard; 8] = precompute_black_forward_rows();
+
+// All squares in front of a square on the same file
+pub const WHITE_FRONT_SPANS: [Bitboard; 64] = precompute_front_spans(WHITE_FORWARD_ROWS);
+pub const BLACK_FRONT_SPANS: [Bitboard; 64] = precompute_front_spans(BLACK_FORWARD_ROWS);
+
+// A pawn is passed if there are no enemy pawns in front of it on its own or adjacent files
+pub const WHITE_PASSED_PAWN_MASKS: [Bitboard; 64] = precompute_passed_pawn_masks(WHITE_FORWARD_ROWS);
+pub const BLACK_PASSED_PAWN_MASKS: [Bitboard; 64] = precompute_passed_pawn_masks(BLACK_FORWARD_ROWS);
+
 pub const BORDER     : Bitboard = 0xff818181818181ff;
 pub const NOT_A_FILE : Bitboard = 0xfefefefefefefefe;
 pub const NOT_H_FILE : Bitboard = 0x7f7f7f7f7f7f7f7f;
@@ -35,6 +51,7 @@ pub const SECOND_ROW : Bitboard = 0x000000000000ff00;
 pub const FOURTH_ROW : Bitboard = 0x00000000ff000000;
 pub const FIFTH_ROW  : Bitboard = 0x000000ff00000000;
 pub const SEVENTH_ROW: Bitboard = 0x00ff000000000000;
+pub const LIGHT_SQUARES: Bitboard = 0x55aa55aa55aa55aa;
 pub const EMPTY      : Bitboard = 0x0000000000000000;
 pub const FULL       : Bitboard = 0xffffffffffffffff;
 
@@ -208,4 +225,84 @@ const fn precompute_black_pawn_captures() -> [Bitboard; 64] {
         sq += 1;
     }
     pawn_captures
-}
\ No newline at end of file
+}
+
+const fn precompute_files() -> [Bitboard; 8] {
+    let mut files = [EMPTY; 8];
+    let mut x = 0;
+    while x < 8 {
+        files[x] = 0x0101010101010101 << x;
+        x += 1;
+    }
+    files
+}
+
+const fn precompute_rows() -> [Bitboard; 8] {
+    let mut rows = [EMPTY; 8];
+    let mut y = 0;
+    while y < 8 {
+        rows[y] = 0xff << (8 * y);
+        y += 1;
+    }
+    rows
+}
+
+const fn precompute_adjacent_files() -> [Bitboard; 8] {
+    let files = precompute_files();
+    let mut adjacent_files = [EMPTY; 8];
+    let mut x = 0;
+    while x < 8 {
+        if x > 0 {
+            adjacent_files[x] |= files[x - 1];
+        }
+        if x < 7 {
+            adjacent_files[x] |= files[x + 1];
+        }
+        x += 1;
+    }
+    adjacent_files
+}
+
+const fn precompute_white_forward_rows() -> [Bitboard; 8] {
+    let mut forward_rows = [EMPTY; 8];
+    let mut y = 0;
+    while y < 7 {
+        forward_rows[y] = FULL << (8 * (y + 1));
+        y += 1;
+    }
+    forward_rows
+}
+
+const fn precompute_black_forward_rows() -> [Bitboard; 8] {
+    let mut forward_rows = [EMPTY; 8];
+    let mut y = 1;
+    while y < 8 {
+        forward_rows[y] = FULL >> (8 * (8 - y));
+        y += 1;
+    }
+    forward_rows
+}
+
+const fn precompute_front_spans(forward_rows: [Bitboard; 8]) -> [Bitboard; 64] {
+    let files = precompute_files();
+    let mut front_spans = [EMPTY; 64];
+    let mut sq = 0;
+    while sq < 64 {
+        front_spans[sq as usize] = files[util::get_square_x(sq) as usize] & forward_rows[util::get_square_y(sq) as usize];
+        sq += 1;
+    }
+    front_spans
+}
+
+const fn precompute_passed_pawn_masks(forward_rows: [Bitboard; 8]) -> [Bitboard; 64] {
+    let files = precompute_files();
+    let adjacent_files = precompute_adjacent_files();
+    let mut passed_pawn_masks = [EMPTY; 64];
+    let mut sq = 0;
+    while sq < 64 {
+        let x = util::get_square_x(sq) as usize;
+        passed_pawn_masks[sq as usize] = (files[x] | adjacent_files[x]) & forward_rows[util::get_square_y(sq) as usize];
+        sq += 1;
+    }
+    passed_pawn_masks
+}
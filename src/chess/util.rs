@@ -1,6 +1,6 @@
 use crate::chess::precomputed;
 
-use super::{Square, Bitboard};
+use super::{Square, Bitboard, Color, Score, CHECKMATE_SCORE};
 
 pub fn print_bb(bb: Bitboard) {
     println!(
@@ -59,6 +59,23 @@ pub const fn get_square_y(square: Square) -> u8 {
     square >> 3
 }
 
+// Number of king moves between two squares
+#[inline(always)]
+pub const fn square_distance(sq1: Square, sq2: Square) -> u8 {
+    let dx = get_square_x(sq1).abs_diff(get_square_x(sq2));
+    let dy = get_square_y(sq1).abs_diff(get_square_y(sq2));
+    if dx > dy {dx} else {dy}
+}
+
+// All squares attacked by the given pawns
+#[inline(always)]
+pub const fn pawn_attacks(pawns: Bitboard, color: Color) -> Bitboard {
+    match color {
+        Color::White => ((pawns & precomputed::NOT_A_FILE) << 7) | ((pawns & precomputed::NOT_H_FILE) << 9),
+        Color::Black => ((pawns & precomputed::NOT_A_FILE) >> 9) | ((pawns & precomputed::NOT_H_FILE) >> 7)
+    }
+}
+
 #[inline(always)]
 pub const fn is_out_of_bounds(x: isize, y: isize) -> bool {
     x < 0 || x >= 8 || y < 0 || y >= 8
@@ -66,4 +83,10 @@ pub const fn is_out_of_bounds(x: isize, y: isize) -> bool {
 
 pub fn piece_name_from_usize(pt: usize) -> char {
     *"PNBRQKpnbrqk".as_bytes().get(pt).expect(&format!("Not a valid piece: {}", pt)) as char
-}
\ No newline at end of file
+}
+
+// Full moves until mate for a mate score (negative when the side of the score gets mated), as in 'score mate' of UCI
+pub fn mate_in_moves(score: Score) -> Option<Score> {
+    let mate_in_plies = CHECKMATE_SCORE - score.abs();
+    (mate_in_plies < 100).then(|| mate_in_plies / 2 * score.signum())
+}
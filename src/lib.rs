@@ -6,7 +6,10 @@ pub const DATE: &str = "2024-02-15";
 use std::error::Error;
 use serde::{Deserialize, Serialize};
 
-pub use chess::{MoveGenerator, Board, MoveList, Perft, ChessEngine, SearchParams, util, PieceType::*, Color::*, Move, Eval, grade};
+pub use chess::{MoveGenerator, Board, MoveList, Perft, Datagen, ChessEngine, SearchParams, util, PieceType::*, Color::*, Move, Eval, grade};
+pub use chess::{CHECKMATE_SCORE, MAX_SKILL_LEVEL, MIN_ELO, MAX_ELO, WdlModel, WdlSample, EvalParams, EvalTrace, EvalBreakdown, EvalTerm, Tuner, nnue};
+pub use chess::{EpdPosition, Game, GameResult, Line, MoveNode, PgnReader, Book, BookEntry, DEFAULT_BOOK_DEPTH};
+pub use chess::{PackedPosition, epd_to_packed, packed_to_epd, PackedReader, PackedWriter, RESULT_BLACK_WIN, RESULT_DRAW, RESULT_WHITE_WIN};
 
 #[allow(dead_code)]
 mod chess;
@@ -52,9 +55,23 @@ cargo test --release -- --nocapture
 
 #[cfg(test)]
 mod tests {
-    use std::fs;
+    use std::{fs, io::Cursor, sync::Arc};
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
 
     use super::*;
+    use nnue::{Network, Accumulator, HIDDEN, INPUTS, QA, QB, SCALE};
+
+    fn random_network(seed: u64) -> Network {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut random = |n: usize, range: i16| (0..n).map(|_| rng.gen_range(-range..=range)).collect::<Box<[i16]>>();
+        Network {
+            feature_weights: random(INPUTS * HIDDEN, 100),
+            feature_bias: random(HIDDEN, 100),
+            output_weights: random(2 * HIDDEN, 100),
+            output_bias: random(1, 1000)[0]
+        }
+    }
 
     #[test]
     fn perft() {
@@ -73,4 +90,726 @@ mod tests {
             }
         }
     }
+
+    // The incrementally updated piece-square eval and pawn key have to match a full recompute after every move and undo.
+    // perft checks this too, but only in debug builds
+    #[test]
+    fn psqt_incremental_matches_refresh() {
+        let mg = MoveGenerator::new();
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        let recomputed = |board: &Board| (board.make_psqt(), board.make_pawn_key());
+
+        let json_str = fs::read_to_string("./test_positions.json").expect("Error loading json file.");
+        let test_positions: Vec<TestPosition> = serde_json::from_str(&json_str).unwrap();
+        for test_position in &test_positions {
+            let mut board = Board::try_from_fen(&test_position.fen).expect("Error loading board from fen.");
+            let mut played = Vec::new();
+            for _ in 0..40 {
+                let moves = {
+                    let mut moves = MoveList::new();
+                    mg.generate_legal_moves(&mut board, &mut moves, false);
+                    moves.collect::<Vec<Move>>()
+                };
+                if moves.is_empty() {
+                    break;
+                }
+                let mv = moves[rng.gen_range(0..moves.len())];
+                board.make_move(&mv);
+                played.push(mv);
+                assert_eq!(((board.mg_score, board.eg_score, board.game_phase), board.pawn_key), recomputed(&board));
+            }
+            while let Some(mv) = played.pop() {
+                board.undo_move(&mv);
+                assert_eq!(((board.mg_score, board.eg_score, board.game_phase), board.pawn_key), recomputed(&board));
+            }
+        }
+    }
+
+    // Legal move by its coordinate notation, with the flags set by the move generator
+    fn legal_move(board: &mut Board, name: &str) -> Move {
+        let mut moves = MoveList::new();
+        MoveGenerator::shared().generate_legal_moves(board, &mut moves, false);
+        moves.find(|mv| mv.to_string() == name).expect("Move should be legal")
+    }
+
+    #[test]
+    fn san() {
+        let cases: [(&str, &str, &str, &[&str]); 11] = [
+            ("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2", "e4d5", "exd5", &["ed5", "e4xd5", "e4-d5"]),
+            ("8/4P3/8/8/8/k7/8/K7 w - - 0 1", "e7e8q", "e8=Q", &["e8Q", "e8=q", "e8q"]),
+            ("rnbqkb1r/ppp1pppp/5n2/3p4/3P4/5N2/PPP1PPPP/RNBQKB1R b KQkq - 2 3", "b8d7", "Nbd7", &["Nb8d7", "Nb8-d7"]),
+            ("rnbqkb1r/ppp1pppp/5n2/3p4/3P4/5N2/PPP1PPPP/RNBQKB1R b KQkq - 2 3", "f6d7", "Nfd7", &[]),
+            ("7k/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3", "R1a3", &["Ra1a3"]),
+            ("2k5/8/8/8/4Q2Q/8/K7/7Q w - - 0 1", "h4e1", "Qh4e1", &[]),
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", "e1g1", "O-O", &["0-0"]),
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", "e1c1", "O-O-O", &["0-0-0"]),
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1", "e8g8", "O-O", &["0-0"]),
+            ("r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5Q2/PPPP1PPP/RNB1K1NR w KQkq - 4 4", "c4f7", "Bxf7+", &["Bxf7", "Bf7!?"]),
+            ("r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5Q2/PPPP1PPP/RNB1K1NR w KQkq - 4 4", "f3f7", "Qxf7#", &["Qxf7", "Qf7+"])
+        ];
+        for (fen, name, san, alternatives) in cases {
+            let mut board = Board::try_from_fen(fen).unwrap();
+            let mv = legal_move(&mut board, name);
+            assert_eq!(mv.to_san(&board), san);
+            for text in std::iter::once(&san).chain(alternatives) {
+                assert_eq!(Move::from_san(text, &board).unwrap(), mv, "{}", text);
+            }
+        }
+
+        let board = Board::try_from_fen("rnbqkb1r/ppp1pppp/5n2/3p4/3P4/5N2/PPP1PPPP/RNBQKB1R b KQkq - 2 3").unwrap();
+        assert!(Move::from_san("Nd7", &board).unwrap_err().to_string().starts_with("Ambiguous move"));
+        assert!(Move::from_san("Nd6", &board).unwrap_err().to_string().starts_with("Illegal move"));
+    }
+
+    const TEST_PGN: &str = r#"[Event "Test"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+1. e4 {best by test} e5 2. Nf3 $1 (2. f4 exf4 (2... d5) 3. Nf3) 2... Nc6
+3. Bb5!? a6 1-0
+
+[Event "Endgame"]
+[SetUp "1"]
+[FEN "8/8/4k3/8/8/3K4/4P3/8 w - - 0 1"]
+[Result "1/2-1/2"]
+
+1. e4 Kd6 1/2-1/2
+
+[Event "Illegal"]
+
+1. e4 e5 2. Ke3 *
+
+[Event "After"]
+
+1. d4 *
+"#;
+
+    fn sans(line: &Line) -> Vec<&str> {
+        line.moves.iter().map(|node| node.san.as_str()).collect()
+    }
+
+    #[test]
+    fn pgn_reader() {
+        let mut games = PgnReader::new(Cursor::new(TEST_PGN));
+
+        let game = games.next().unwrap().unwrap();
+        assert_eq!(game.header("White"), Some("A"));
+        assert_eq!(game.header("Black"), Some("B"));
+        assert_eq!(game.result, GameResult::WhiteWin);
+        assert_eq!(sans(&game.mainline), ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"]);
+        let moves = &game.mainline.moves;
+        assert_eq!(moves[0].comment.as_deref(), Some("best by test"));
+        assert_eq!((moves[2].nags.as_slice(), moves[4].nags.as_slice()), ([1].as_slice(), [5].as_slice()));
+        assert_eq!(moves[2].variations.len(), 1);
+        let variation = &moves[2].variations[0];
+        assert_eq!(sans(variation), ["f4", "exf4", "Nf3"]);
+        assert_eq!(variation.moves[1].variations.len(), 1);
+        assert_eq!(sans(&variation.moves[1].variations[0]), ["d5"]);
+
+        let game = games.next().unwrap().unwrap();
+        assert_eq!(game.start_fen, "8/8/4k3/8/8/3K4/4P3/8 w - - 0 1");
+        assert_eq!(sans(&game.mainline), ["e4", "Kd6"]);
+        assert_eq!(game.result, GameResult::Draw);
+
+        let error = games.next().unwrap().unwrap_err().to_string();
+        assert!(error.starts_with("Illegal move: Ke3") && error.ends_with("(ply 3)"), "{}", error);
+
+        let game = games.next().unwrap().unwrap();
+        assert_eq!(game.header("Event"), Some("After"));
+        assert_eq!(sans(&game.mainline), ["d4"]);
+        assert_eq!(game.result, GameResult::Unknown);
+        assert!(games.next().is_none());
+    }
+
+    #[test]
+    fn pgn_writer_round_trip() {
+        for game in PgnReader::new(Cursor::new(TEST_PGN)).filter_map(Result::ok) {
+            let written = game.to_string();
+            let reread = PgnReader::new(Cursor::new(written.as_str())).next().unwrap().unwrap();
+            assert_eq!(sans(&reread.mainline), sans(&game.mainline));
+            assert_eq!((reread.result, reread.start_fen.as_str()), (game.result, game.start_fen.as_str()));
+            assert_eq!(reread.header("Event"), game.header("Event"));
+            assert_eq!(reread.to_string(), written);
+        }
+    }
+
+    #[test]
+    fn pgn_long_game() {
+        // 600 plies of knight shuffles, with a variation on the last move
+        let movetext = (0..150)
+            .map(|i| format!("{}. Nf3 Nf6 {}. Ng1 Ng8", 2 * i + 1, 2 * i + 2))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let pgn = format!("[Event \"Long\"]\n\n{} (300... Nh5) 301. e4 1/2-1/2\n", movetext);
+
+        let game = PgnReader::new(Cursor::new(pgn)).next().unwrap().unwrap();
+        assert_eq!(game.mainline.moves.len(), 601);
+        assert_eq!(sans(&game.mainline)[596..], ["Nf3", "Nf6", "Ng1", "Ng8", "e4"]);
+        assert_eq!(sans(&game.mainline.moves[599].variations[0]), ["Nh5"]);
+        assert_eq!(game.result, GameResult::Draw);
+
+        let mut board = game.start_board().unwrap();
+        game.mainline_moves().for_each(|mv| board.make_move(&mv));
+        assert_eq!(board.get_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 0");
+    }
+
+    #[test]
+    fn pgn_from_moves() {
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        let mut board = Board::try_from_fen(fen).unwrap();
+        let moves = "e7e5 g1f3 b8c6 f1b5 a7a6 b5a4 g8f6 e1g1 f8e7 f1e1 b7b5 a4b3 d7d6 c2c3 e8g8 h2h3 c6a5 b3c2 c7c5 d2d4"
+            .split_ascii_whitespace()
+            .map(|name| {
+                let mv = legal_move(&mut board, name);
+                board.make_move(&mv);
+                mv
+            })
+            .collect::<Vec<Move>>();
+
+        let mut game = Game::from_moves(fen, &moves).unwrap();
+        game.set_header("White", "Engine");
+        let text = game.to_string();
+        assert!(text.contains(&format!("[White \"Engine\"]\n[Black \"?\"]\n[Result \"*\"]\n[SetUp \"1\"]\n[FEN \"{}\"]\n\n", fen)));
+
+        let movetext = text.split("\n\n").nth(1).unwrap();
+        assert!(movetext.starts_with("1... e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O Be7"));
+        assert!(movetext.ends_with("11. d4 *\n"));
+        assert!(movetext.lines().count() > 1 && movetext.lines().all(|line| line.len() <= 80));
+    }
+
+    #[test]
+    fn epd() {
+        let names = |moves: &[Move]| moves.iter().map(|mv| mv.to_string()).collect::<Vec<String>>();
+
+        let wac = EpdPosition::parse(r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001; quoted";"#).unwrap();
+        assert_eq!(wac.fen, "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1");
+        assert_eq!(names(&wac.best_moves), ["g3g6"]);
+        assert_eq!(wac.id.as_deref(), Some("WAC.001; quoted"));
+        assert!(wac.avoid_moves.is_empty() && wac.direct_mate.is_none());
+
+        let am = EpdPosition::parse("r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/2N2N2/PPPP1PPP/R1BQK2R w KQkq - am Nxe5; hmvc 4; fmvn 5;").unwrap();
+        assert_eq!(names(&am.avoid_moves), ["f3e5"]);
+        assert_eq!(am.fen, "r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/2N2N2/PPPP1PPP/R1BQK2R w KQkq - 4 5");
+
+        let dm = EpdPosition::parse("k7/8/1K6/8/8/8/8/7R w - - dm 1; acd 12").unwrap();
+        assert_eq!((dm.direct_mate, dm.depth), (Some(1), Some(12)));
+
+        let sts = EpdPosition::parse(r#"1kr5/3n4/q3p2p/p2n2p1/PppB1P2/5BP1/1P2Q2P/3R2K1 w - - bm f5; id "STS(v1.0) Undermine.001"; c0 "f5=10, Be5+=2, Bf2=3, Bg4=2";"#).unwrap();
+        assert_eq!(names(&sts.best_moves), ["f4f5"]);
+        assert_eq!(sts.comment.as_deref(), Some("f5=10, Be5+=2, Bf2=3, Bg4=2"));
+        assert_eq!(sts.operation("id"), Some(["STS(v1.0) Undermine.001".to_string()].as_slice()));
+        assert!(EpdPosition::parse("k7/8/1K6/8/8/8/8/7R w - - bm Rh9;").is_err());
+
+        // Invalid lines are skipped
+        let path = std::env::temp_dir().join("peripheral_epd_load.epd");
+        let path = path.to_str().unwrap();
+        fs::write(path, "k7/8/1K6/8/8/8/8/7R w - - dm 1;\n\nk7/8/1K6/8/8/8/8/7R w - - bm Rh9;\nnot an epd\n8/8/8/8/8/8/8/8\n").unwrap();
+        let loaded = EpdPosition::load(path);
+        fs::write(path, "not an epd\n").unwrap();
+        let invalid = EpdPosition::load(path);
+        fs::remove_file(path).unwrap();
+        assert_eq!(loaded.unwrap().iter().map(|epd| epd.direct_mate).collect::<Vec<_>>(), [Some(1)]);
+        assert!(invalid.is_err());
+
+        let board = Board::try_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -").unwrap();
+        let full = Board::try_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert_eq!((board.key, board.get_fen()), (full.key, full.get_fen()));
+    }
+
+    // Reference keys of the Polyglot book format specification
+    #[test]
+    fn polyglot_key() {
+        let cases = [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 0x463b96181691fc9c),
+            ("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1", 0x823c9b50fd114196),
+            ("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2", 0x0756b94461c50fb0),
+            ("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2", 0x662fafb965db29d4),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", 0x22a48b5a8e47ff78),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 0 3", 0x652a607ca3f242c1),
+            ("rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - - 0 4", 0x00fdd303c946bdd9),
+            ("rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3", 0x3c8123ea7b067637),
+            ("rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4", 0x5c3f9b829b279560)
+        ];
+        for (fen, key) in cases {
+            assert_eq!(Board::try_from_fen(fen).unwrap().polyglot_key(), key, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn book() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut board = Board::try_from_fen(fen).unwrap();
+        let key = board.polyglot_key();
+        let mg = MoveGenerator::shared();
+
+        // (key, move, weight) sorted by key: e1h1 is castling, a1a8 is blocked by the own pawn on a2
+        let entries = [(key - 1, 0x0001, 9), (key, 0x0107, 3), (key, 0x0210, 1), (key, 0x0038, 5), (key + 1, 0x0001, 9)];
+        let bytes = entries.iter().flat_map(|&(key, mv, weight): &(u64, u16, u16)| {
+            [&key.to_be_bytes()[..], &mv.to_be_bytes(), &weight.to_be_bytes(), &0u32.to_be_bytes()].concat()
+        }).collect::<Vec<u8>>();
+        let book = Book::from_bytes(&bytes).unwrap();
+        assert_eq!(book.len(), 5);
+        assert!(Book::from_bytes(&bytes[1..]).is_err());
+
+        let names = book.entries(key).iter().map(|entry| entry.move_name(&board)).collect::<Vec<String>>();
+        assert_eq!(names, ["e1g1", "a2a3", "a1a8"]);
+        assert!(book.entries(key + 2).is_empty());
+
+        let moves = book.moves(&mut board, mg).iter().map(|(mv, weight)| (mv.to_string(), *weight)).collect::<Vec<(String, u16)>>();
+        assert_eq!(moves, [(String::from("e1g1"), 3), (String::from("a2a3"), 1)]);
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let picks = (0..100).map(|_| book.pick_move(&mut board, mg, &mut rng).unwrap().to_string()).collect::<Vec<String>>();
+        assert!(picks.iter().all(|name| name == "e1g1" || name == "a2a3"));
+        assert!(picks.iter().filter(|name| *name == "e1g1").count() > picks.iter().filter(|name| *name == "a2a3").count());
+        assert_eq!(board.get_fen(), Board::try_from_fen(fen).unwrap().get_fen());
+    }
+
+    // White is lost, but has a perpetual check: Qe8+ Kh7 Qh5+ Kg8
+    #[test]
+    fn contempt_draw_score() {
+        let mut engine = ChessEngine::new("6k1/r2Q2p1/8/8/8/8/1q3PPP/6K1 w - - 0 1", 16);
+        engine.set_contempt(50);
+        let mut search_params = SearchParams::new();
+        search_params.depth = 8;
+        let (mv, score) = engine.search(search_params, false);
+        assert_eq!((mv.to_string(), score), (String::from("d7e8"), 0));
+
+        engine.make_move(mv);
+        assert!(engine.game().unwrap().to_string().contains("[%eval 0.00,8]"));
+    }
+
+    // Saved weights load back the same, and an eval with other weights doesn't change the default one
+    #[test]
+    fn eval_params_json_round_trip() {
+        let mut params = EvalParams::DEFAULT;
+        params.piece_values[WPawn as usize].eg += 40;
+        params.knight_mobility[3].mg -= 7;
+        params.safety_table[10].eg += 5;
+        params.min_king_attackers = 3;
+        params.update_pqts();
+
+        let path = std::env::temp_dir().join("peripheral_eval_params_round_trip.json");
+        let path = path.to_str().unwrap();
+        params.save(path).unwrap();
+        let loaded = EvalParams::load(path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(loaded.to_json().unwrap(), params.to_json().unwrap());
+        assert!(loaded.pqts == params.pqts);
+
+        let mg = MoveGenerator::new();
+        let mut board = Board::try_from_fen("8/4k3/8/8/3P4/8/4K3/8 w - - 0 1").unwrap();
+        let default_score = Eval::new().breakdown(&board, &mg).score;
+        let mut eval = Eval::with_params(Arc::new(loaded));
+        board.refresh_psqt(eval.params().clone());
+        assert_eq!(eval.breakdown(&board, &mg).score, default_score + 40);
+
+        let board = Board::try_from_fen("8/4k3/8/8/3P4/8/4K3/8 w - - 0 1").unwrap();
+        assert_eq!(Eval::new().breakdown(&board, &mg).score, default_score);
+    }
+
+    // The same position with the colours swapped and the board mirrored vertically
+    fn flipped_fen(fen: &str) -> String {
+        let fields = fen.split(' ').collect::<Vec<&str>>();
+        let swap_case = |s: &str| s.chars().map(|c| if c.is_ascii_uppercase() {c.to_ascii_lowercase()} else {c.to_ascii_uppercase()}).collect::<String>();
+        let placement = fields[0].split('/').rev().map(swap_case).collect::<Vec<String>>().join("/");
+        let player = if fields[1] == "w" {"b"} else {"w"};
+        let mut castling = swap_case(fields[2]).chars().collect::<Vec<char>>();
+        castling.sort_by_key(|&c| "KQkq-".find(c));
+        let en_passant = fields[3].replace('3', "x").replace('6', "3").replace('x', "6");
+        format!("{} {} {} {} {}", placement, player, castling.into_iter().collect::<String>(), en_passant, fields[4..].join(" "))
+    }
+
+    // Breakdown of the handcrafted eval, checked to be the same for both colours
+    fn symmetric_breakdown(fen: &str) -> EvalBreakdown {
+        let mg = MoveGenerator::shared();
+        let breakdown = Eval::new().breakdown(&Board::try_from_fen(fen).unwrap(), mg);
+        let flipped = Eval::new().breakdown(&Board::try_from_fen(&flipped_fen(fen)).unwrap(), mg);
+        assert_eq!(flipped.score, -breakdown.score, "{}", fen);
+        assert_eq!(flipped.scale_factor, breakdown.scale_factor, "{}", fen);
+        for (term, flipped_term) in breakdown.terms.iter().zip(&flipped.terms) {
+            assert_eq!((flipped_term.white, flipped_term.black), (term.black, term.white), "{} {}", term.name, fen);
+        }
+        breakdown
+    }
+
+    #[test]
+    fn endgame_evaluators() {
+        let score = |fen: &str| symmetric_breakdown(fen).score;
+
+        // KBNK with a light squared bishop, the lone king walks towards a8
+        let kbnk = ["8/8/8/4k3/8/3K4/8/5BN1 w - - 0 1", "8/8/2k5/8/8/3K4/8/5BN1 w - - 0 1", "8/1k6/8/8/8/3K4/8/5BN1 w - - 0 1", "k7/8/8/8/8/3K4/8/5BN1 w - - 0 1"];
+        assert!(symmetric_breakdown(kbnk[0]).endgame_evaluator);
+        assert!(kbnk.windows(2).all(|w| score(w[0]) > 0 && score(w[0]) < score(w[1])));
+        assert!(score("8/8/8/8/8/3K4/8/k4BN1 w - - 0 1") < score(kbnk[3]), "a1 is the wrong corner");
+
+        // KQK and KRK, the lone king walks towards the edge
+        for piece in ['Q', 'R'] {
+            let mop_up = ["1X6/8/4K3/8/4k3/8/8/8 w - - 0 1", "1X6/8/4K3/8/8/4k3/8/8 w - - 0 1", "1X6/8/4K3/8/8/8/4k3/8 w - - 0 1", "1X6/8/4K3/8/8/8/8/4k3 w - - 0 1"]
+                .map(|fen| fen.replace('X', &piece.to_string()));
+            assert!(symmetric_breakdown(&mop_up[0]).endgame_evaluator);
+            assert!(mop_up.windows(2).all(|w| score(&w[0]) > 0 && score(&w[0]) < score(&w[1])));
+        }
+
+        // KRKP, won with the strong king in front of the pawn, drawish with an advanced pawn supported by its king
+        assert!(symmetric_breakdown("7k/8/8/8/2p5/8/2K5/7R w - - 0 1").endgame_evaluator);
+        assert!(score("7k/8/8/8/2p5/8/2K5/7R w - - 0 1") > 400);
+        assert!((0..100).contains(&score("7K/8/8/8/8/8/2pk4/R7 w - - 0 1")));
+
+        // Bishop and rook pawn, drawn when the bishop doesn't control the promotion square
+        let wrong_bishop = symmetric_breakdown("7k/8/6KP/8/8/8/8/5B2 w - - 0 1");
+        let right_bishop = symmetric_breakdown("7k/8/6KP/8/8/8/8/4B3 w - - 0 1");
+        assert_eq!((wrong_bishop.scale_factor, right_bishop.scale_factor), (0, 64));
+        assert!(wrong_bishop.score >= 0 && wrong_bishop.score < right_bishop.score / 10);
+
+        // Opposite coloured bishops
+        let opposite_bishops = symmetric_breakdown("8/5k2/8/2b1P3/1PB5/5K2/8/8 w - - 0 1");
+        let same_bishops = symmetric_breakdown("8/5k2/8/3bP3/1PB5/5K2/8/8 w - - 0 1");
+        assert_eq!((opposite_bishops.scale_factor, same_bishops.scale_factor), (22, 64));
+        assert!(opposite_bishops.score > 0 && opposite_bishops.score < same_bishops.score);
+
+        // Pawnless, up a single minor piece at most
+        let minor_up = symmetric_breakdown("4k3/8/8/3r4/8/3B4/8/3RK3 w - - 0 1");
+        assert_eq!(minor_up.scale_factor, 8);
+        assert!(minor_up.score > 0 && minor_up.score < 150);
+        let lone_knight = symmetric_breakdown("4k3/8/8/8/8/3N4/8/4K3 w - - 0 1");
+        assert_eq!(lone_knight.scale_factor, 0);
+        assert!(lone_knight.score >= 0 && lone_knight.score < 30);
+    }
+
+    #[test]
+    fn wdl_model() {
+        let model = WdlModel::new();
+        for phase in [0, 12, 24] {
+            let mut previous = (0, 0, 1000);
+            for score in (-1000..=1000).step_by(10) {
+                let (win, draw, loss) = model.wdl(score, phase);
+                assert_eq!(win + draw + loss, 1000);
+                assert_eq!(model.wdl(-score, phase), (loss, draw, win));
+                assert!(win >= previous.0 && loss <= previous.2, "{} {}", score, phase);
+                previous = (win, draw, loss);
+            }
+        }
+        assert_eq!(model.wdl(CHECKMATE_SCORE - 5, 12), (1000, 0, 0));
+        assert_eq!(model.wdl(-CHECKMATE_SCORE + 5, 12), (0, 0, 1000));
+
+        // Results drawn from a known model. In the endgame only the constant coefficients matter
+        let target = WdlModel { a: [100.0, 0.0, 0.0, 0.0], b: [70.0, 0.0, 0.0, 0.0] };
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        let samples = (0..5000).map(|_| {
+            let score = rng.gen_range(-500..=500);
+            let (win, _, loss) = target.wdl(score, 0);
+            let r = rng.gen_range(0..1000);
+            let result = if r < win {1.0} else if r < 1000 - loss {0.5} else {0.0};
+            WdlSample { score, phase: 0, result }
+        }).collect::<Vec<WdlSample>>();
+
+        let fitted = model.fit(&samples);
+        assert!((fitted.a[0] - target.a[0]).abs() < 10.0 && (fitted.b[0] - target.b[0]).abs() < 10.0, "{:?}", fitted);
+    }
+
+    // Trace of the handcrafted eval, checked to be the same for both colours
+    fn symmetric_trace(fen: &str) -> EvalTrace {
+        let mg = MoveGenerator::shared();
+        let mut trace = Eval::new().trace(&Board::try_from_fen(fen).unwrap(), mg);
+        let mut flipped = Eval::new().trace(&Board::try_from_fen(&flipped_fen(fen)).unwrap(), mg);
+        assert_eq!(flipped.coefficients(), trace.coefficients().iter().map(|count| -count).collect::<Vec<i32>>(), "{}", fen);
+        symmetric_breakdown(fen);
+        trace
+    }
+
+    fn term(breakdown: &EvalBreakdown, name: &str) -> EvalTerm {
+        *breakdown.terms.iter().find(|term| term.name == name).unwrap()
+    }
+
+    #[test]
+    fn pawn_structure() {
+        // White: an isolated a-pawn, doubled c-pawns of which c3 is backward (c4 is attacked by b5), three islands
+        let fen = "4k3/5ppp/8/1p6/2PP4/2P5/P4PPP/4K3 w - - 0 1";
+        let trace = symmetric_trace(fen);
+        let [white, black] = &trace.counts;
+        assert_eq!((white.doubled_pawn.mg, white.isolated_pawn.mg, white.backward_pawn.mg, white.pawn_island.mg), (1, 1, 1, 3));
+        assert_eq!((black.doubled_pawn.mg, black.isolated_pawn.mg, black.backward_pawn.mg, black.pawn_island.mg), (0, 1, 0, 2));
+        // c4 and d4 next to each other, d4 supported by c3, and the f-h pawns
+        assert_eq!((white.connected_pawn[3].mg, white.connected_pawn[1].mg), (2, 3));
+
+        let pawn_structure = term(&symmetric_breakdown(fen), "Pawn structure").total();
+        assert!(pawn_structure.mg < 0 && pawn_structure.eg < 0);
+
+        // A pawn table hit, also from a position with other pieces, gives the same eval as a fresh computation
+        let mg = MoveGenerator::shared();
+        let other_pieces = Board::try_from_fen("r3k3/5ppp/8/1p6/2PP4/2P2N2/P4PPP/4K2R b - - 0 1").unwrap();
+        let board = Board::try_from_fen(fen).unwrap();
+        assert_eq!(other_pieces.pawn_key, board.pawn_key);
+        let fresh = Eval::new().eval(&board, mg);
+        let mut eval = Eval::new();
+        eval.eval(&other_pieces, mg);
+        assert_eq!(eval.eval(&board, mg), fresh);
+        assert_eq!(eval.eval(&board, mg), fresh);
+    }
+
+    #[test]
+    fn passed_pawns() {
+        // The a-pawn is outside the square of the black king with white to move, inside it with black to move
+        let outside = symmetric_trace("8/8/4k3/P7/8/8/8/7K w - - 0 1");
+        let inside = symmetric_trace("8/8/4k3/P7/8/8/8/7K b - - 0 1");
+        assert_eq!((outside.counts[0].unstoppable_passed_pawn.mg, inside.counts[0].unstoppable_passed_pawn.mg), (1, 0));
+        assert_eq!(outside.counts[0].passed_pawn[4].mg, 1);
+        assert_eq!((outside.counts[0].passed_pawn_free_path[4].mg, outside.counts[0].passed_pawn_safe_path[4].mg), (1, 1));
+        // Kings on e6 and h1, the pawn promotes on a8
+        assert_eq!(outside.counts[0].passed_pawn_enemy_king_distance[4].mg, 4);
+        assert_eq!(outside.counts[0].passed_pawn_own_king_distance[4].mg, 5);
+        let (outside, inside) = (symmetric_breakdown("8/8/4k3/P7/8/8/8/7K w - - 0 1"), symmetric_breakdown("8/8/4k3/P7/8/8/8/7K b - - 0 1"));
+        assert!(term(&inside, "Passed pawns").total().eg > 0);
+        assert!(term(&outside, "Passed pawns").total().eg > term(&inside, "Passed pawns").total().eg + 500);
+
+        // A pawn on its starting row can move two squares at once, so a2 needs five moves like a3
+        assert_eq!(symmetric_trace("8/8/8/6k1/8/8/P7/7K w - - 0 1").counts[0].unstoppable_passed_pawn.mg, 1);
+        assert_eq!(symmetric_trace("8/8/8/5k2/8/8/P7/7K w - - 0 1").counts[0].unstoppable_passed_pawn.mg, 0);
+
+        // With a knight left, the king isn't the only defender. The blockade on d7 takes away the free and safe path
+        let blocked = symmetric_trace("4k3/3n4/3P4/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(blocked.counts[0].unstoppable_passed_pawn.mg, 0);
+        assert_eq!(blocked.counts[0].passed_pawn[5].mg, 1);
+        assert_eq!((blocked.counts[0].passed_pawn_free_path[5].mg, blocked.counts[0].passed_pawn_safe_path[5].mg), (0, 0));
+    }
+
+    #[test]
+    fn mobility_and_pieces() {
+        // A knight outpost on d5, rooks on open files of which one on the seventh, and the bishop pair
+        let fen = "6k1/ppR2ppp/8/3N4/4P3/4B3/4BPPP/3R2K1 w - - 0 1";
+        let trace = symmetric_trace(fen);
+        let [white, black] = &trace.counts;
+        assert_eq!((white.knight_outpost.mg, white.rook_open_file.mg, white.rook_on_seventh.mg, white.bishop_pair.mg), (1, 2, 1, 1));
+        assert_eq!((black.knight_outpost.mg, black.rook_open_file.mg, black.rook_on_seventh.mg, black.bishop_pair.mg), (0, 0, 0, 0));
+        // b4, c3, e7 and f4: c7 and e3 are taken by own pieces, b6 and f6 are attacked by enemy pawns
+        assert_eq!(white.knight_mobility[4].mg, 1);
+
+        let breakdown = symmetric_breakdown(fen);
+        assert!(term(&breakdown, "Pieces").total().mg > 0 && term(&breakdown, "Mobility").total().mg > 0);
+
+        // The e7 pawn can chase the knight away, and the rook on c7 stays on a semi-open file
+        let trace = symmetric_trace("6k1/p1R1pppp/2p5/3N4/4P3/4B3/4BPPP/3R2K1 w - - 0 1");
+        assert_eq!((trace.counts[0].knight_outpost.mg, trace.counts[0].rook_semi_open_file.mg), (0, 1));
+    }
+
+    // The entry of the safety table that is used for the king of each color
+    fn attack_units(trace: &EvalTrace) -> [usize; 2] {
+        trace.counts.each_ref().map(|counts| counts.safety_table.iter().position(|entry| entry.mg == -1).unwrap())
+    }
+
+    #[test]
+    fn king_safety() {
+        // Full pawn shields and no attackers
+        assert_eq!(attack_units(&symmetric_trace("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1")), [0, 0]);
+
+        // The white king has an open g-file (2) without a shield pawn (2) and an advanced h-pawn (1).
+        // The rook attacks f2 and f3 (2 * 3), the knight f3 and g2 (2 * 2)
+        let fen = "1k3r2/ppp5/8/8/7n/7P/5P2/6K1 w - - 0 1";
+        assert_eq!(attack_units(&symmetric_trace(fen)), [15, 0]);
+        let king_safety = term(&symmetric_breakdown(fen), "King safety").total();
+        assert!(king_safety.mg < 0 && king_safety.eg < 0);
+
+        // A single attacker doesn't count
+        assert_eq!(attack_units(&symmetric_trace("1k3r2/ppp5/8/8/8/7P/5P2/6K1 w - - 0 1")), [5, 0]);
+    }
+
+    #[test]
+    fn threats() {
+        // The d5 pawn attacks the knight, the knight attacks the defended rook and the bishop the undefended queen
+        let fen = "6k1/1p3ppp/2n5/3Pr3/q7/1B3N2/P4PPP/6K1 w - - 0 1";
+        let trace = symmetric_trace(fen);
+        let [white, black] = &trace.counts;
+        assert_eq!((white.threat_by_pawn.mg, white.threat_by_minor[WRook as usize].mg, white.threat_by_minor[WQueen as usize].mg), (1, 1, 1));
+        assert_eq!((white.threat_by_rook.mg, white.hanging_piece.mg, white.pawn_push_threat.mg), (0, 1, 0));
+        // The queen attacks the bishop, which is defended by the a-pawn
+        assert_eq!((black.threat_by_pawn.mg, black.hanging_piece.mg, black.pawn_push_threat.mg), (0, 0, 0));
+        assert!(term(&symmetric_breakdown(fen), "Threats").total().mg > 0);
+
+        // e4-e5 attacks the knight, g2-g4 would lose the pawn
+        assert_eq!(symmetric_trace("6k1/5ppp/5n2/8/4P3/8/5PPP/6K1 w - - 0 1").counts[0].pawn_push_threat.mg, 1);
+    }
+
+    // The weakened engine chooses between all root moves, also when the root is an exact node in the transposition table
+    #[test]
+    fn skill_after_exact_tt_hit() {
+        let picks = (0..20).map(|_| {
+            let mut engine = ChessEngine::new("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3", 16);
+            let mut search_params = SearchParams::new();
+            search_params.depth = 5;
+            engine.search(search_params, false);
+
+            // The engine's move and a reply it searched with a full window
+            engine.make_uci_move("b1c3").unwrap();
+            engine.make_uci_move("f8c5").unwrap();
+            assert!(engine.probe_tt().contains("type:\texact"));
+
+            engine.set_skill_level(0);
+            engine.search(SearchParams::new(), false).0.to_string()
+        }).collect::<std::collections::HashSet<String>>();
+        assert!(picks.len() > 1, "{:?}", picks);
+    }
+
+    // The terms of the breakdown, scaled and tapered, add up to the eval
+    #[test]
+    fn breakdown_adds_up() {
+        let mg = MoveGenerator::shared();
+        let mut rng = ChaCha8Rng::seed_from_u64(6);
+        let json_str = fs::read_to_string("./test_positions.json").expect("Error loading json file.");
+        let test_positions: Vec<TestPosition> = serde_json::from_str(&json_str).unwrap();
+        let mut checked = 0;
+        for test_position in &test_positions {
+            let mut board = Board::try_from_fen(&test_position.fen).expect("Error loading board from fen.");
+            for _ in 0..20 {
+                let breakdown = Eval::new().breakdown(&board, mg);
+                if !breakdown.endgame_evaluator {
+                    let mut total = breakdown.total();
+                    total.eg = total.eg * breakdown.scale_factor / 64;
+                    assert_eq!(total.taper(breakdown.phase), breakdown.score, "{}", board.get_fen());
+                    assert_eq!(breakdown.score, Eval::new().eval(&board, mg));
+                    checked += 1;
+                }
+
+                let mut moves = MoveList::new();
+                mg.generate_legal_moves(&mut board, &mut moves, false);
+                let moves = moves.collect::<Vec<Move>>();
+                if moves.is_empty() {
+                    break;
+                }
+                board.make_move(&moves[rng.gen_range(0..moves.len())]);
+            }
+        }
+        assert!(checked > 500);
+    }
+
+    // Short games that all reach the ply cap, and are drawn because of it
+    #[test]
+    fn datagen_ply_cap() {
+        let path = std::env::temp_dir().join("peripheral_datagen_ply_cap.bin");
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+        let datagen = Datagen { games: 4, threads: 2, nodes: 500, depth: 4, random_plies: 6, max_plies: 16, seed: 3 };
+        let (games, positions) = datagen.run(path).unwrap();
+        assert_eq!(games, 4);
+        assert!(positions > 0 && positions <= 4 * (16 - 6), "{}", positions);
+
+        let bytes = fs::read(path).unwrap();
+        assert_eq!(bytes.len(), positions * PackedPosition::SIZE);
+        for chunk in bytes.chunks_exact(PackedPosition::SIZE) {
+            let position = PackedPosition::from_bytes(chunk.try_into().unwrap()).unwrap();
+            assert_eq!(position.to_bytes().as_slice(), chunk);
+            assert_eq!(position.result, RESULT_DRAW);
+            // The board has no move counters
+            let mut repacked = PackedPosition::new(&position.to_board().unwrap(), position.score, position.result);
+            repacked.halfmove_clock = position.halfmove_clock;
+            assert_eq!(repacked, position);
+        }
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn puzzles() {
+        let mut engine = ChessEngine::new("8/8/8/8/8/8/8/K6k w - - 0 1", 16);
+        let back_rank = "3r2k1/5ppp/8/8/8/8/5PPP/R5K1 b - - 0 1";
+
+        // Ra8+ Rd8 Rxd8#
+        assert_eq!(engine.solve_puzzle(back_rank, "d8d7 a1a8 d7d8 a8d8", 100_000), Some(true));
+        assert_eq!(engine.get_board().get_fen(), "3R2k1/5ppp/8/8/8/8/5PPP/6K1 b - - 0 0");
+
+        // The engine plays Ra8+ instead of g3, which does not mate
+        assert_eq!(engine.solve_puzzle(back_rank, "d8d7 g2g3 h7h6", 100_000), Some(false));
+        assert_eq!(engine.get_board().get_fen(), "R5k1/3r1ppp/8/8/8/8/5PPP/6K1 b - - 0 0");
+
+        // A mate in one instead of the mate in two of the solution
+        let two_rooks = "6k1/5ppp/8/8/8/8/5PPP/RR4K1 b - - 0 1";
+        assert_eq!(engine.solve_puzzle(two_rooks, "g8h8 a1a7 h7h6 b1b8", 100_000), Some(true));
+        assert!(engine.get_board().gs.is_in_check && *engine.legal_moves().get_count() == 0);
+
+        // Illegal moves and a missing solver move
+        assert_eq!(engine.solve_puzzle(back_rank, "d8d7 a1a9", 100_000), None);
+        assert_eq!(engine.solve_puzzle(back_rank, "d8d7", 100_000), None);
+        assert_eq!(engine.solve_puzzle("not a fen", "d8d7 a1a8", 100_000), None);
+    }
+
+    // The board does not keep track of the move counters, so the positions start with '0 1'
+    #[test]
+    fn packed_round_trip() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 1",
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b Kq e3 0 1",
+            "8/8/4k3/8/8/4K3/8/8 b - - 0 1"
+        ];
+        for (fen, result) in fens.into_iter().zip([RESULT_WHITE_WIN, RESULT_DRAW, RESULT_BLACK_WIN, RESULT_DRAW]) {
+            let board = Board::try_from_fen(fen).unwrap();
+            let packed = PackedPosition::new(&board, -123, result);
+            let unpacked = PackedPosition::from_bytes(&packed.to_bytes()).unwrap();
+            assert_eq!(unpacked, packed);
+            assert_eq!(unpacked.to_fen(), fen);
+            assert_eq!(unpacked.to_board().unwrap().key, board.key);
+
+            let mut packed = packed;
+            (packed.halfmove_clock, packed.fullmove_number) = (7, 42);
+            assert_eq!(PackedPosition::from_epd(&packed.to_epd()).unwrap(), packed);
+        }
+    }
+
+    #[test]
+    fn nnue_matches_scalar_reference() {
+        let network = random_network(1);
+        let board = Board::try_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let accumulator = Accumulator::refresh(&network, &board);
+
+        for (color, us) in [(White, 0), (Black, 1)] {
+            let mut sum = network.output_bias as i32;
+            for (perspective, offset) in [(us, 0), (1 - us, HIDDEN)] {
+                for i in 0..HIDDEN {
+                    let value = (accumulator.values[perspective][i] as i32 + network.feature_bias[i] as i32).clamp(0, QA);
+                    sum += value * network.output_weights[offset + i] as i32;
+                }
+            }
+            assert_eq!(network.evaluate(&accumulator, color) as i32, sum * SCALE / (QA * QB));
+        }
+
+        let bytes = network.to_bytes();
+        let loaded = Network::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.evaluate(&accumulator, White), network.evaluate(&accumulator, White));
+        assert!(Network::from_bytes(&bytes[1..]).is_err());
+    }
+
+    // The incrementally updated accumulator has to match a full refresh after every move and undo
+    #[cfg(feature = "nnue")]
+    #[test]
+    fn nnue_incremental_matches_refresh() {
+        let network = Arc::new(random_network(2));
+        let mg = MoveGenerator::new();
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+
+        let json_str = fs::read_to_string("./test_positions.json").expect("Error loading json file.");
+        let test_positions: Vec<TestPosition> = serde_json::from_str(&json_str).unwrap();
+        for test_position in &test_positions {
+            let mut board = Board::try_from_fen(&test_position.fen).expect("Error loading board from fen.");
+            board.refresh_accumulator(Some(network.clone()));
+            let mut played = Vec::new();
+            for _ in 0..40 {
+                let moves = {
+                    let mut moves = MoveList::new();
+                    mg.generate_legal_moves(&mut board, &mut moves, false);
+                    moves.collect::<Vec<Move>>()
+                };
+                if moves.is_empty() {
+                    break;
+                }
+                let mv = moves[rng.gen_range(0..moves.len())];
+                board.make_move(&mv);
+                played.push(mv);
+                assert_eq!(board.accumulator, Accumulator::refresh(&network, &board));
+            }
+            while let Some(mv) = played.pop() {
+                board.undo_move(&mv);
+                assert_eq!(board.accumulator, Accumulator::refresh(&network, &board));
+            }
+        }
+    }
 }
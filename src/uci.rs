@@ -1,5 +1,5 @@
-use peripheral::{ChessEngine, Board, Perft, Eval, SearchParams};
-use std::{io, str::SplitAsciiWhitespace};
+use peripheral::{ChessEngine, Board, MoveGenerator, Perft, Datagen, EpdPosition, Move, Book, util, epd_to_packed, packed_to_epd, Eval, EvalParams, Tuner, SearchParams, MAX_SKILL_LEVEL, MIN_ELO, MAX_ELO, DEFAULT_BOOK_DEPTH, WdlModel, WdlSample, White};
+use std::{io::{self, BufRead, BufReader}, fs::File, str::{SplitAsciiWhitespace, FromStr}, time::Instant, collections::BTreeMap};
 
 const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 const KIWIPETE_FEN: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
@@ -8,18 +8,41 @@ const DEFAULT_TABLE_SIZE: usize = 16;
 const MIN_TABLE_SIZE: usize = 1;
 const MAX_TABLE_SIZE: usize = 1024;
 
+const DEFAULT_MOVE_OVERHEAD: u128 = 10;
+const MIN_MOVE_OVERHEAD: u128 = 0;
+const MAX_MOVE_OVERHEAD: u128 = 5000;
+
+const DEFAULT_CONTEMPT: i16 = 0;
+const MIN_CONTEMPT: i16 = -100;
+const MAX_CONTEMPT: i16 = 100;
+
+const MAX_BOOK_DEPTH: u8 = 100;
+
+const DEFAULT_TUNE_EPOCHS: usize = 1000;
+
+const DEFAULT_TESTSUITE_MOVETIME: u128 = 1000;
+
+const DEFAULT_PUZZLE_NODES: u64 = 100_000;
+const PUZZLE_RATING_BAND: u32 = 200;
+const PUZZLE_REPORT_INTERVAL: usize = 500;
+const TUNE_REPORT_INTERVAL: usize = 100;
+
 pub struct Uci {
     engine: ChessEngine,
-    ready: bool,
-    table_size: usize
+    table_size: usize,
+    move_overhead: u128,
+    own_book: bool,
+    book_file: String
 }
 
 impl Uci {
     pub fn new() -> Self {
         Self {
             engine: ChessEngine::new(START_FEN, DEFAULT_TABLE_SIZE),
-            ready: true,
-            table_size: DEFAULT_TABLE_SIZE
+            table_size: DEFAULT_TABLE_SIZE,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            own_book: false,
+            book_file: String::new()
         }
     }
 
@@ -55,6 +78,14 @@ impl Uci {
                     "probe"      => self.probe(),
                     "gen"        => self.gen(),
                     "hist"       => self.hist(),
+                    "pgn"        => self.pgn(),
+                    "wdlfit"     => self.wdlfit(&mut args),
+                    "evalsave"   => self.evalsave(&mut args),
+                    "tune"       => self.tune(&mut args),
+                    "datagen"    => self.datagen(&mut args),
+                    "convert"    => self.convert(&mut args),
+                    "testsuite"  => self.testsuite(&mut args),
+                    "puzzles"    => self.puzzles(&mut args),
                     "quit"       => break,
                     other => println!("Unknown command: '{}'. Type 'help' for a list of commands.", other)
                 }
@@ -73,13 +104,21 @@ List of known commands:
 - position   Set a position
 - go         Start thinking
 - d          Print current board
-- eval       Static eval of position
+- eval       Static eval of position, split up into its terms
 - run        Run main function of the bot
 - make       Make move
 - undo       Undo last move made
 - probe      Probe current position in the transposition table
 - gen        Get the TT generation of the last search
 - hist       Print the history of stored keys for threefold detection
+- pgn        Print the moves made since the last position command as PGN, with the evals of the searches that picked them
+- wdlfit     Fit the WDL model on a file of 'fen | score | result' lines
+- evalsave   Write the current eval weights to a JSON file (loadable with the EvalFile option)
+- tune       Tune the eval weights on a file of positions with game results: tune <file> <output> [epochs]
+- datagen    Append self-play positions to a packed file: datagen <output> [games n] [threads n] [nodes n] [depth n] [randomplies n] [maxplies n] [seed n]
+- convert    Convert between EPD and packed positions, by extension: convert <input.epd> <output> or convert <input> <output.epd>
+- testsuite  Run an EPD test suite with bm/am/dm operations: testsuite <file> [movetime ms | nodes n | depth n]
+- puzzles    Solve puzzles from the Lichess puzzle database CSV: puzzles <file> [nodes n] [count n]
 - quit       Quit.", 
             Self::get_header()
         );
@@ -90,60 +129,173 @@ List of known commands:
 id author {}
 
 option name Hash type spin default {} min {} max {}
+option name Move Overhead type spin default {} min {} max {}
+option name Skill Level type spin default {} min 0 max {}
+option name UCI_LimitStrength type check default false
+option name UCI_Elo type spin default {} min {} max {}
+option name Contempt type spin default {} min {} max {}
+option name UCI_ShowWDL type check default false
+option name EvalFile type string default <empty>
+option name OwnBook type check default false
+option name BookFile type string default <empty>
+option name BookDepth type spin default {} min 0 max {}
 uciok",
             peripheral::NAME, peripheral::VERSION,
             peripheral::AUTHOR,
 
-            DEFAULT_TABLE_SIZE, MIN_TABLE_SIZE, MAX_TABLE_SIZE
+            DEFAULT_TABLE_SIZE, MIN_TABLE_SIZE, MAX_TABLE_SIZE,
+            DEFAULT_MOVE_OVERHEAD, MIN_MOVE_OVERHEAD, MAX_MOVE_OVERHEAD,
+            MAX_SKILL_LEVEL, MAX_SKILL_LEVEL,
+            MAX_ELO, MIN_ELO, MAX_ELO,
+            DEFAULT_CONTEMPT, MIN_CONTEMPT, MAX_CONTEMPT,
+            DEFAULT_BOOK_DEPTH, MAX_BOOK_DEPTH
         );
     }
 
+    // Commands are handled one at a time, so the engine is always ready when this is read
     fn isready(&self) {
-        while !self.ready {}
         println!("readyok");
     }
 
     fn setoption(&mut self, args: &mut SplitAsciiWhitespace) {
-        self.ready = false;
-
         args.next(); // "name"
-        let name = args.next();
-        args.next(); // "value"
+        let name = args.by_ref().take_while(|x| !(*x).eq("value")).collect::<Vec<&str>>().join(" "); // names may contain spaces
         let Some(value) = args.next() else {
             println!("No value given");
             return;
         };
-        let Ok(value) = value.parse::<usize>() else {
-            println!("Invalid value");
+        let value = std::iter::once(value).chain(args).collect::<Vec<&str>>().join(" "); // paths may contain spaces
+        let value = value.as_str();
+
+        match name.to_ascii_lowercase().as_str() {
+            "hash" => {
+                    let Some(value) = Self::parse_spin(value, MIN_TABLE_SIZE, MAX_TABLE_SIZE) else {
+                        return;
+                    };
+                    self.table_size = value;
+                    println!("Set hash size to {}mb", self.table_size);
+                },
+            "move overhead" => {
+                    let Some(value) = Self::parse_spin(value, MIN_MOVE_OVERHEAD, MAX_MOVE_OVERHEAD) else {
+                        return;
+                    };
+                    self.move_overhead = value;
+                },
+            "skill level" => {
+                    let Some(value) = Self::parse_spin(value, 0, MAX_SKILL_LEVEL) else {
+                        return;
+                    };
+                    self.engine.set_skill_level(value);
+                },
+            "uci_limitstrength" => {
+                    let Some(value) = Self::parse_check(value) else {
+                        return;
+                    };
+                    self.engine.set_limit_strength(value);
+                },
+            "uci_elo" => {
+                    let Some(value) = Self::parse_spin(value, MIN_ELO, MAX_ELO) else {
+                        return;
+                    };
+                    self.engine.set_elo(value);
+                },
+            "contempt" => {
+                    let Some(value) = Self::parse_spin(value, MIN_CONTEMPT, MAX_CONTEMPT) else {
+                        return;
+                    };
+                    self.engine.set_contempt(value);
+                },
+            "uci_showwdl" => {
+                    let Some(value) = Self::parse_check(value) else {
+                        return;
+                    };
+                    self.engine.set_show_wdl(value);
+                },
+            "evalfile" => {
+                    if value.is_empty() || value == "<empty>" {
+                        self.engine.set_eval_params(EvalParams::DEFAULT);
+                        #[cfg(feature = "nnue")]
+                        self.engine.set_network(None);
+                    } else if cfg!(feature = "nnue") && value.ends_with(".nnue") {
+                        #[cfg(feature = "nnue")]
+                        match peripheral::nnue::Network::load(value) {
+                            Ok(network) => self.engine.set_network(Some(network)),
+                            Err(e) => println!("info string Could not load network {}: {}", value, e)
+                        }
+                    } else {
+                        match EvalParams::load(value) {
+                            Ok(params) => self.engine.set_eval_params(params),
+                            Err(e) => println!("info string Could not load eval file {}: {}", value, e)
+                        }
+                    }
+                },
+            "ownbook" => {
+                    let Some(value) = Self::parse_check(value) else {
+                        return;
+                    };
+                    self.own_book = value;
+                    self.load_book();
+                },
+            "bookfile" => {
+                    self.book_file = if value == "<empty>" { String::new() } else { value.to_string() };
+                    self.load_book();
+                },
+            "bookdepth" => {
+                    let Some(value) = Self::parse_spin(value, 0, MAX_BOOK_DEPTH) else {
+                        return;
+                    };
+                    self.engine.set_book_depth(value);
+                },
+            _ => ()
+        }
+    }
+
+    // The book is only loaded while OwnBook is on and a file is set
+    fn load_book(&mut self) {
+        if !self.own_book || self.book_file.is_empty() {
+            self.engine.set_book(None);
             return;
+        }
+        match Book::open(&self.book_file) {
+            Ok(book) => {
+                println!("info string Loaded book {} with {} entries", self.book_file, book.len());
+                self.engine.set_book(Some(book));
+            },
+            Err(e) => {
+                println!("info string Could not load book {}: {}", self.book_file, e);
+                self.engine.set_book(None);
+            }
+        }
+    }
+
+    fn parse_spin<T: FromStr + PartialOrd>(value: &str, min: T, max: T) -> Option<T> {
+        let Ok(value) = value.parse::<T>() else {
+            println!("Invalid value");
+            return None;
         };
+        if value < min || value > max {
+            println!("Value not within required bounds");
+            return None;
+        }
+        Some(value)
+    }
 
-        if let Some(name) = name {
-            match name.to_ascii_lowercase().as_str() {
-                "hash" => {
-                        if value < MIN_TABLE_SIZE || value > MAX_TABLE_SIZE {
-                            println!("Hash size not within required bounds");
-                            return;
-                        }
-                        self.table_size = value;
-                        println!("Set hash size to {}mb", self.table_size);
-                    },
-                _ => ()
+    fn parse_check(value: &str) -> Option<bool> {
+        match value {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => {
+                println!("Invalid value");
+                None
             }
         }
-
-        self.ready = true;
     }
 
     fn ucinewgame(&mut self) {
-        self.ready = false;
         self.engine.reset_table(self.table_size);
-        self.ready = true;
     }
 
     fn position(&mut self, args: &mut SplitAsciiWhitespace) {
-        self.ready = false;
-
         let start_fen = if let Some(pos_type) = args.next() {
             match pos_type {
                 "startpos" => {
@@ -180,12 +332,11 @@ uciok",
                 }
             };
         }
-
-        self.ready = true;
     }
 
     fn go(&mut self, args: &mut SplitAsciiWhitespace) {
         let mut search_params = SearchParams::new();
+        search_params.move_overhead = self.move_overhead;
         while let Some(a) = args.next() {
             match a {
                 "perft" => {
@@ -199,7 +350,9 @@ uciok",
                 "btime" => search_params.btime = args.next().expect("no btime given").parse::<i128>().expect("btime not an integer").max(0) as u128,
                 "winc"  => search_params.winc  = args.next().expect("no winc given").parse::<u128>().expect("winc not an integer"),
                 "binc"  => search_params.binc  = args.next().expect("no binc given").parse::<u128>().expect("binc not an integer"),
+                "movestogo" => search_params.moves_to_go = Some(args.next().expect("no movestogo given").parse::<u128>().expect("movestogo not an integer")),
                 "depth" => search_params.depth = args.next().expect("no depth given").parse::<u8>().expect("depth not a byte"),
+                "nodes" => search_params.nodes = args.next().expect("no nodes given").parse::<u64>().expect("nodes not an integer"),
                 _ => ()
             }
         }
@@ -211,8 +364,17 @@ uciok",
     }
 
     fn eval(&self) {
-        let board = Board::try_from_fen(self.engine.get_board().get_fen().as_str()).expect("Engine returned an incorrect fen");
-        println!("{}", Eval::eval(&board));
+        let mut board = Board::try_from_fen(self.engine.get_board().get_fen().as_str()).expect("Engine returned an incorrect fen");
+        let mut eval = Eval::with_params(self.engine.eval_params().clone());
+        board.refresh_psqt(eval.params().clone());
+        let mg = MoveGenerator::new();
+        println!("{}", eval.breakdown(&board, &mg));
+        #[cfg(feature = "nnue")]
+        if let Some(network) = self.engine.network() {
+            eval.set_network(Some(network.clone()));
+            board.refresh_accumulator(Some(network.clone()));
+            println!("NNUE evaluation: {} (white side)", eval.eval(&board, &mg));
+        }
     }
 
     fn run_bot(&self) {
@@ -245,7 +407,320 @@ uciok",
         println!("{}", self.engine.get_gen())
     }
 
+    fn pgn(&self) {
+        match self.engine.game() {
+            Ok(game) => print!("{}", game),
+            Err(e) => println!("Could not write PGN: {}", e)
+        }
+    }
+
     fn hist(&self) {
         self.engine.print_history();
     }
+
+    // Each line contains a position, its score and the game result, all from white's perspective: 'fen | 35 | 1.0'
+    fn wdlfit(&mut self, args: &mut SplitAsciiWhitespace) {
+        let Some(path) = args.next() else {
+            println!("No file given");
+            return;
+        };
+        let Ok(file) = File::open(path) else {
+            println!("Could not open {}", path);
+            return;
+        };
+
+        let mut samples = Vec::new();
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            let mut parts = line.split('|').map(str::trim);
+            let (Some(fen), Some(score), Some(result)) = (parts.next(), parts.next(), parts.next()) else {
+                continue;
+            };
+            let (Ok(board), Ok(score), Ok(result)) = (Board::try_from_fen(fen), score.parse::<i16>(), result.parse::<f64>()) else {
+                println!("Skipping invalid line: {}", line);
+                continue;
+            };
+
+            let white_to_move = board.gs.player_to_move == White;
+            samples.push(WdlSample {
+                score: if white_to_move {score} else {-score},
+                phase: Eval::game_phase(&board),
+                result: if white_to_move {result} else {1.0 - result}
+            });
+        }
+
+        println!("Fitting WDL model on {} positions...", samples.len());
+        let model = WdlModel::new().fit(&samples);
+        println!("a: {:?}\nb: {:?}", model.a, model.b);
+        self.engine.set_wdl_model(model);
+    }
+
+    fn evalsave(&self, args: &mut SplitAsciiWhitespace) {
+        let Some(path) = args.next() else {
+            println!("No file given");
+            return;
+        };
+        match self.engine.eval_params().save(path) {
+            Ok(()) => println!("Saved eval weights to {}", path),
+            Err(e) => println!("Could not save eval weights: {}", e)
+        }
+    }
+
+    fn tune(&mut self, args: &mut SplitAsciiWhitespace) {
+        let (Some(path), Some(output)) = (args.next(), args.next()) else {
+            println!("Usage: tune <file> <output> [epochs]");
+            return;
+        };
+        let epochs = args.next().and_then(|x| x.parse::<usize>().ok()).unwrap_or(DEFAULT_TUNE_EPOCHS);
+
+        let mut tuner = match Tuner::load(path, self.engine.eval_params()) {
+            Ok(tuner) => tuner,
+            Err(e) => {
+                println!("Could not load {}: {}", path, e);
+                return;
+            }
+        };
+        println!("Tuning on {} positions", tuner.len());
+        println!("K: {:.4}", tuner.fit_k());
+        tuner.tune(epochs, TUNE_REPORT_INTERVAL);
+
+        let params = tuner.params();
+        match params.save(output) {
+            Ok(()) => println!("Saved eval weights to {}", output),
+            Err(e) => println!("Could not save eval weights: {}", e)
+        }
+        self.engine.set_eval_params(params);
+    }
+
+    fn datagen(&self, args: &mut SplitAsciiWhitespace) {
+        let Some(output) = args.next() else {
+            println!("Usage: datagen <output> [games n] [threads n] [nodes n] [depth n] [randomplies n] [maxplies n] [seed n]");
+            return;
+        };
+
+        let mut datagen = Datagen::new();
+        while let (Some(option), Some(value)) = (args.next(), args.next()) {
+            let parsed = match option {
+                "games"       => value.parse().map(|x| datagen.games = x).is_ok(),
+                "threads"     => value.parse().map(|x| datagen.threads = x).is_ok(),
+                "nodes"       => value.parse().map(|x| datagen.nodes = x).is_ok(),
+                "depth"       => value.parse().map(|x| datagen.depth = x).is_ok(),
+                "randomplies" => value.parse().map(|x| datagen.random_plies = x).is_ok(),
+                "maxplies"    => value.parse().map(|x| datagen.max_plies = x).is_ok(),
+                "seed"        => value.parse().map(|x| datagen.seed = x).is_ok(),
+                _ => false
+            };
+            if !parsed {
+                println!("Invalid datagen option: {} {}", option, value);
+                return;
+            }
+        }
+
+        println!("Playing {} games on {} threads", datagen.games, datagen.threads);
+        match datagen.run(output) {
+            Ok((games, positions)) => println!("Wrote {} positions from {} games to {}", positions, games, output),
+            Err(e) => println!("Could not write to {}: {}", output, e)
+        }
+    }
+
+    fn convert(&self, args: &mut SplitAsciiWhitespace) {
+        let (Some(input), Some(output)) = (args.next(), args.next()) else {
+            println!("Usage: convert <input.epd> <output> or convert <input> <output.epd>");
+            return;
+        };
+
+        let converted = if input.ends_with(".epd") {
+            epd_to_packed(input, output)
+        } else if output.ends_with(".epd") {
+            packed_to_epd(input, output)
+        } else {
+            println!("Either the input or the output should be an .epd file");
+            return;
+        };
+        match converted {
+            Ok(count) => println!("Converted {} positions to {}", count, output),
+            Err(e) => println!("Could not convert {}: {}", input, e)
+        }
+    }
+
+    fn testsuite(&mut self, args: &mut SplitAsciiWhitespace) {
+        let Some(path) = args.next() else {
+            println!("Usage: testsuite <file> [movetime ms | nodes n | depth n]");
+            return;
+        };
+
+        let (mut move_time, mut nodes, mut depth) = (None, u64::MAX, u8::MAX);
+        while let (Some(limit), Some(value)) = (args.next(), args.next()) {
+            let parsed = match limit {
+                "movetime" => value.parse().map(|x| move_time = Some(x)).is_ok(),
+                "nodes"    => value.parse().map(|x| nodes = x).is_ok(),
+                "depth"    => value.parse().map(|x| depth = x).is_ok(),
+                _ => false
+            };
+            if !parsed {
+                println!("Invalid testsuite limit: {} {}", limit, value);
+                return;
+            }
+        }
+        if move_time.is_none() && nodes == u64::MAX && depth == u8::MAX {
+            move_time = Some(DEFAULT_TESTSUITE_MOVETIME);
+        }
+
+        let positions = match EpdPosition::load(path) {
+            Ok(positions) => positions,
+            Err(e) => {
+                println!("Could not load {}: {}", path, e);
+                return;
+            }
+        };
+
+        let start = Instant::now();
+        let (mut tested, mut solved, mut points, mut max_points) = (0, 0, 0, 0);
+        for (i, epd) in positions.iter().enumerate() {
+            self.engine.set_board(&epd.fen).expect("Parsed EPD should have a valid fen");
+            self.engine.reset_table(self.table_size);
+            let mut search_params = SearchParams::new();
+            search_params.move_time = move_time;
+            search_params.nodes = nodes;
+            search_params.depth = depth;
+            search_params.move_overhead = self.move_overhead;
+            let (mv, score) = self.engine.search_full_strength(search_params, false);
+            let board = self.engine.get_board();
+
+            // STS style scores in the comment: "Qxe5=10, f5=3"
+            if let Some(scores) = epd.comment.as_deref().and_then(|comment| Self::parse_move_scores(comment, board)) {
+                points += scores.iter().find(|(scored, _)| *scored == mv).map_or(0, |(_, points)| *points);
+                max_points += scores.iter().map(|(_, points)| *points).max().unwrap_or(0);
+            }
+
+            let mut expected = Vec::new();
+            if !epd.best_moves.is_empty() {
+                expected.push(format!("bm {}", epd.best_moves.iter().map(|mv| mv.to_san(board)).collect::<Vec<String>>().join(" ")));
+            }
+            if !epd.avoid_moves.is_empty() {
+                expected.push(format!("am {}", epd.avoid_moves.iter().map(|mv| mv.to_san(board)).collect::<Vec<String>>().join(" ")));
+            }
+            if let Some(mate) = epd.direct_mate {
+                expected.push(format!("dm {}", mate));
+            }
+            if expected.is_empty() {
+                continue;
+            }
+
+            // Only a mate for the side to move counts for dm
+            let passed = (epd.best_moves.is_empty() || epd.best_moves.contains(&mv))
+                && !epd.avoid_moves.contains(&mv)
+                && epd.direct_mate.is_none_or(|mate| util::mate_in_moves(score).is_some_and(|moves| moves > 0 && moves as u32 <= mate));
+            tested += 1;
+            if passed {
+                solved += 1;
+            }
+            println!("{:>4}/{} {:<12} {:<7} {:<8} {}",
+                i + 1, positions.len(),
+                epd.id.as_deref().unwrap_or("-"),
+                if passed {"solved"} else {"FAILED"},
+                mv.to_san(board),
+                expected.join("; ")
+            );
+        }
+
+        println!();
+        println!("Solved {}/{} ({:.1}%) in {:.1}s", solved, tested, 100.0 * solved as f32 / tested.max(1) as f32, start.elapsed().as_secs_f32());
+        if max_points > 0 {
+            println!("Points {}/{} ({:.1}%)", points, max_points, 100.0 * points as f32 / max_points as f32);
+        }
+    }
+
+    // Moves with a score, e.g. "Qxe5=10, f5=3". None unless the whole comment is in this format
+    fn parse_move_scores(comment: &str, board: &Board) -> Option<Vec<(Move, u32)>> {
+        comment.split(',').map(|entry| {
+            let (san, points) = entry.trim().rsplit_once('=')?;
+            Some((Move::from_san(san, board).ok()?, points.trim().parse().ok()?))
+        }).collect()
+    }
+
+    fn puzzles(&mut self, args: &mut SplitAsciiWhitespace) {
+        let Some(path) = args.next() else {
+            println!("Usage: puzzles <file> [nodes n] [count n]");
+            return;
+        };
+
+        let (mut nodes, mut count) = (DEFAULT_PUZZLE_NODES, usize::MAX);
+        while let (Some(option), Some(value)) = (args.next(), args.next()) {
+            let parsed = match option {
+                "nodes" => value.parse().map(|x| nodes = x).is_ok(),
+                "count" => value.parse().map(|x| count = x).is_ok(),
+                _ => false
+            };
+            if !parsed {
+                println!("Invalid puzzles option: {} {}", option, value);
+                return;
+            }
+        }
+
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                println!("Could not open {}: {}", path, e);
+                return;
+            }
+        };
+
+        let start = Instant::now();
+        let (mut solved, mut total, mut skipped) = (0, 0, 0);
+        let mut bands = BTreeMap::<u32, (usize, usize)>::new();
+        let mut themes = BTreeMap::<String, (usize, usize)>::new();
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            if total >= count {
+                break;
+            }
+            // PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags
+            let fields = line.split(',').collect::<Vec<&str>>();
+            if fields.len() < 8 || fields[0] == "PuzzleId" {
+                continue;
+            }
+            let Ok(rating) = fields[3].parse::<u32>() else {
+                skipped += 1;
+                continue;
+            };
+            self.engine.reset_table(self.table_size);
+            let Some(passed) = self.engine.solve_puzzle(fields[1], fields[2], nodes) else {
+                skipped += 1;
+                continue;
+            };
+
+            total += 1;
+            solved += passed as usize;
+            let band = bands.entry(rating / PUZZLE_RATING_BAND * PUZZLE_RATING_BAND).or_default();
+            band.0 += passed as usize;
+            band.1 += 1;
+            for theme in fields[7].split_ascii_whitespace() {
+                let theme = themes.entry(theme.to_string()).or_default();
+                theme.0 += passed as usize;
+                theme.1 += 1;
+            }
+            if total % PUZZLE_REPORT_INTERVAL == 0 {
+                println!("{} puzzles | solved {:.1}%", total, 100.0 * solved as f32 / total as f32);
+            }
+        }
+
+        let row = |name: &str, (solved, total): (usize, usize)| {
+            println!("{:<24} {:>6}/{:<6} {:>5.1}%", name, solved, total, 100.0 * solved as f32 / total.max(1) as f32);
+        };
+        println!();
+        println!("Rating");
+        for (band, result) in bands {
+            row(&format!("{}-{}", band, band + PUZZLE_RATING_BAND - 1), result);
+        }
+        println!();
+        println!("Theme");
+        for (theme, result) in themes {
+            row(&theme, result);
+        }
+        println!();
+        if skipped > 0 {
+            println!("Skipped {} invalid puzzles", skipped);
+        }
+        row("Total", (solved, total));
+        println!("Time: {:.1}s", start.elapsed().as_secs_f32());
+    }
 }